@@ -0,0 +1,81 @@
+use rand::Rng;
+
+/// Walker's alias method, built with Vose's algorithm.
+///
+/// Construction is O(n); each sample is O(1) and uses two random draws: one to
+/// pick a column and one to choose between the column and its alias.
+#[derive(Clone, Debug)]
+pub struct AliasTable {
+    prob: Vec<f64>,
+    alias: Vec<usize>,
+}
+impl AliasTable {
+    /// Build a table over `weights`. Returns `None` if there is no positive weight to sample from.
+    pub fn new(weights: &[f64]) -> Option<Self> {
+        let n = weights.len();
+        let total: f64 = weights.iter().sum();
+        if n == 0 || total.is_nan() || total <= 0.0 {
+            return None;
+        }
+        // Scale so the average column holds exactly 1.0.
+        let mut scaled: Vec<f64> = weights.iter().map(|w| w * n as f64 / total).collect();
+        let mut prob = vec![0.0; n];
+        let mut alias = vec![0; n];
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) =
+            (0..n).partition(|&i| scaled[i] < 1.0);
+        while let (Some(&s), Some(&l)) = (small.last(), large.last()) {
+            small.pop();
+            prob[s] = scaled[s];
+            alias[s] = l;
+            // Donate enough of `l` to top `s` up to 1.0.
+            scaled[l] -= 1.0 - scaled[s];
+            if scaled[l] < 1.0 {
+                large.pop();
+                small.push(l);
+            }
+        }
+        // Whatever remains is 1.0 up to floating point error.
+        for i in small.into_iter().chain(large) {
+            prob[i] = 1.0;
+            alias[i] = i;
+        }
+        Some(Self { prob, alias })
+    }
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let i = rng.gen_range(0..self.prob.len());
+        if rng.gen::<f64>() < self.prob[i] {
+            i
+        } else {
+            self.alias[i]
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    #[test]
+    fn matches_input_distribution() {
+        let weights = [1.0, 0.0, 3.0, 6.0];
+        let table = AliasTable::new(&weights).unwrap();
+        let mut prng = SmallRng::seed_from_u64(42);
+        let iterations = 1_000_000;
+        let mut tally = [0usize; 4];
+        for _ in 0..iterations {
+            tally[table.sample(&mut prng)] += 1;
+        }
+        assert_eq!(tally[1], 0);
+        for (i, &w) in weights.iter().enumerate() {
+            let observed = tally[i] as f64 / iterations as f64;
+            assert!((observed - w / 10.0).abs() < 0.005, "[{i}] {observed}");
+        }
+    }
+
+    #[test]
+    fn rejects_empty_weights() {
+        assert!(AliasTable::new(&[]).is_none());
+        assert!(AliasTable::new(&[0.0, 0.0]).is_none());
+    }
+}
@@ -0,0 +1,143 @@
+//! Exact expected load, computed from each client's routing weights instead of
+//! by sampling.
+
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    matrix::TrafficMatrix,
+    sim::{self, Strategy},
+    Backend, BackendId, LoadBalancer, Zone, DEFAULT_SEED,
+};
+
+/// The expected distribution of traffic, as fractions of all requests.
+#[derive(Clone, Debug)]
+pub struct Solution {
+    pub backends: BTreeMap<BackendId, Backend>,
+    /// The fraction of all requests each backend receives.
+    pub share: BTreeMap<BackendId, f64>,
+    /// The fraction of all requests sent from each client zone to each backend zone.
+    pub flows: TrafficMatrix,
+    pub in_zone_fraction: f64,
+}
+impl Solution {
+    /// Each backend's share of requests relative to its share of capacity, as
+    /// in [`crate::sim::Report::normalized_load`].
+    pub fn normalized_load(&self) -> BTreeMap<BackendId, f64> {
+        let shares = self.share.iter().map(|(&id, &share)| (id, share));
+        sim::normalized_load(shares, &self.backends)
+    }
+}
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (id, load) in self.normalized_load() {
+            writeln!(f, "[{}] {load:.5}", self.backends[&id].zone)?;
+        }
+        write!(f, "% in-zone = {}", self.in_zone_fraction)
+    }
+}
+
+/// Combine the routing weights of a client in every zone of `demand`, weighted
+/// by that zone's share of demand.
+pub fn solve<L: LoadBalancer>(backends: &[Backend], demand: &BTreeMap<Zone, f64>) -> Solution {
+    let fleet = sim::fleet::<L>(backends, demand, DEFAULT_SEED);
+    let total_demand: f64 = demand.values().sum();
+    let mut share: BTreeMap<BackendId, f64> = backends.iter().map(|b| (b.id, 0.0)).collect();
+    for client in &fleet {
+        for (id, w) in client.lb.weights() {
+            *share.entry(id).or_default() += client.rate / total_demand * w;
+        }
+    }
+    let flows = TrafficMatrix::from_weights(&fleet, backends);
+    Solution {
+        backends: backends.iter().map(|b| (b.id, b.clone())).collect(),
+        share,
+        in_zone_fraction: flows.in_zone_fraction(),
+        flows,
+    }
+}
+
+/// [`solve`] for a strategy chosen at runtime.
+///
+/// # Panics
+///
+/// If `strategy` [needs feedback](Strategy::needs_feedback), since its
+/// weights alone do not say where its requests go.
+pub fn solve_strategy(
+    strategy: Strategy,
+    backends: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+) -> Solution {
+    sim::assert_no_feedback(strategy);
+    sim::with_strategy!(strategy, L => solve::<L>(backends, demand))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Picker;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    #[test]
+    fn picker_is_exactly_uniform_on_random_topologies() {
+        let mut prng = SmallRng::seed_from_u64(1);
+        for _ in 0..2_000 {
+            let num_zones = prng.gen_range(1..=6u8);
+            let mut backends = Vec::new();
+            let mut demand = BTreeMap::new();
+            for z in 0..num_zones {
+                let zone = Zone(b'a' + z);
+                for _ in 0..prng.gen_range(0..8) {
+                    backends.push(Backend {
+                        id: BackendId(backends.len() as u32),
+                        zone,
+                        capacity: prng.gen_range(0.5..4.0),
+                    });
+                }
+                demand.insert(zone, prng.gen_range(0.1..10.0));
+            }
+            if backends.is_empty() {
+                continue;
+            }
+            let solution = solve::<Picker>(&backends, &demand);
+            for (id, load) in solution.normalized_load() {
+                assert!(
+                    (load - 1.0).abs() < 1e-9,
+                    "{id:?}: load = {load}, {backends:?} {demand:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn matches_monte_carlo() {
+        let backends = sim::topology(&[
+            (Zone(b'a'), 1, 1.0),
+            (Zone(b'b'), 5, 1.0),
+            (Zone(b'c'), 9, 1.0),
+        ]);
+        let demand = [
+            (Zone(b'a'), 2.0),
+            (Zone(b'b'), 1.0),
+            (Zone(b'c'), 1.0),
+            (Zone(b'd'), 1.0),
+        ]
+        .into_iter()
+        .collect();
+        let solution = solve::<Picker>(&backends, &demand);
+        let report = sim::run_with_demand::<Picker>(&backends, &demand, 100_000, 42);
+        let diff = (solution.in_zone_fraction - report.in_zone_fraction()).abs();
+        assert!(
+            diff < 0.005,
+            "{} vs {}",
+            solution.in_zone_fraction,
+            report.in_zone_fraction()
+        );
+        for ((from, to), share) in solution.flows.iter() {
+            let observed = report.flows.get(from, to) / report.total as f64;
+            assert!(
+                (share - observed).abs() < 0.005,
+                "{from}->{to}: {share} vs {observed}"
+            );
+        }
+    }
+}
@@ -0,0 +1,634 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::{Args, Parser, Subcommand};
+use lb_simulations::{
+    cost::CostModel,
+    des::DesConfig,
+    distribution::Distribution,
+    export::{self, Format, Record},
+    json::Value,
+    keyed::{self, KeyedStrategy},
+    network::RttModel,
+    scenario::{Scenario, ZoneSpec, DEFAULT_ITERATIONS},
+    sim::{self, Strategy},
+    Backend, BackendId, Zone, DEFAULT_SEED,
+};
+
+/// Simulate client-side zone-aware load balancing.
+#[derive(Parser)]
+#[command(name = "lb-sim")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run one scenario and print per-backend load and the in-zone fraction.
+    Run {
+        #[command(flatten)]
+        scenario: ScenarioArgs,
+        /// Emit structured records instead of a human-readable summary.
+        #[arg(long)]
+        format: Option<Format>,
+    },
+    /// Vary the number of backends in one zone and print one summary line per step.
+    Sweep {
+        #[command(flatten)]
+        scenario: ScenarioArgs,
+        /// The zone whose backend count is swept.
+        #[arg(long, default_value = "a")]
+        zone: char,
+        #[arg(long, default_value_t = 1)]
+        from: usize,
+        #[arg(long, default_value_t = 10)]
+        to: usize,
+        #[arg(
+            long,
+            default_value_t = 1,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        step: usize,
+        /// Emit structured records for every step instead of a summary table.
+        #[arg(long)]
+        format: Option<Format>,
+    },
+    /// Compute the exact expected per-backend load and in-zone fraction without sampling.
+    Solve(ScenarioArgs),
+    /// Print the zone-to-zone traffic matrix as fractions of all requests.
+    Matrix {
+        #[command(flatten)]
+        scenario: ScenarioArgs,
+        /// Compute the matrix from the pickers' weights instead of by sampling.
+        #[arg(long)]
+        exact: bool,
+    },
+    /// Simulate arrivals, queueing and service over time and report utilization and latency.
+    Des {
+        #[command(flatten)]
+        scenario: ScenarioArgs,
+        #[command(flatten)]
+        des: DesArgs,
+    },
+    /// Route synthetic request keys and report load spread and how many keys
+    /// move when a backend is added or removed.
+    Keyed {
+        #[command(flatten)]
+        scenario: ScenarioArgs,
+        /// Keyed strategies to compare.
+        #[arg(long, value_delimiter = ',', default_value = "ring")]
+        hash: Vec<KeyedStrategy>,
+        /// Number of distinct keys to route.
+        #[arg(long, default_value_t = 100_000)]
+        keys: u64,
+        /// Also measure keys moved over this many alternating backend
+        /// removals and additions.
+        #[arg(long, default_value_t = 0)]
+        churn: usize,
+        /// Send each client zone's keys to its own zone first, spilling
+        /// cross-zone like the picker, and hash within the chosen zone.
+        #[arg(long)]
+        zonal: bool,
+    },
+    /// Sweep the headroom of consistent hashing with bounded loads and print
+    /// cache affinity against maximum load for Zipf-distributed keys.
+    Bounded {
+        #[command(flatten)]
+        scenario: ScenarioArgs,
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_value = "0.05,0.1,0.25,0.5,1,1000"
+        )]
+        epsilon: Vec<f64>,
+        /// Number of distinct keys.
+        #[arg(long, default_value_t = 10_000)]
+        keys: u64,
+        /// Requests to route, each for a key drawn from the Zipf distribution.
+        #[arg(long, default_value_t = 100_000)]
+        requests: u64,
+        /// Zipf exponent of key popularity; 0 makes every key equally popular.
+        #[arg(long, default_value_t = 1.0)]
+        zipf: f64,
+    },
+    /// Compare how evenly strategies spread load over short windows of
+    /// consecutive requests.
+    Window {
+        #[command(flatten)]
+        scenario: ScenarioArgs,
+        /// Strategies to compare.
+        #[arg(long, value_delimiter = ',', default_value = "picker,smooth-wrr,edf")]
+        compare: Vec<Strategy>,
+        /// Requests per window, summed over every client.
+        #[arg(long, default_value_t = 1_000)]
+        window: u64,
+        /// Number of consecutive windows to measure.
+        #[arg(long, default_value_t = 100)]
+        windows: u64,
+    },
+    /// Print the scenario described by the flags as a scenario file.
+    Dump(ScenarioArgs),
+}
+
+#[derive(Args, Clone)]
+struct ScenarioArgs {
+    /// Load the topology from a scenario file instead of the flags below.
+    /// --iterations, --seed and --strategy still override the file.
+    #[arg(long)]
+    scenario: Option<PathBuf>,
+    /// Number of zones with backends, named a, b, c, ...
+    #[arg(long, default_value_t = 3)]
+    zones: usize,
+    /// Backends in each zone. A single value applies to every zone.
+    #[arg(long, value_delimiter = ',', default_value = "1,5,9")]
+    backends_per_zone: Vec<usize>,
+    /// Capacity of each backend, per zone. A single value applies to every zone.
+    #[arg(long, value_delimiter = ',', default_value = "1.0")]
+    capacity: Vec<f64>,
+    /// Zones that have clients but no backends, named after the backend zones.
+    #[arg(long, default_value_t = 0)]
+    backendless_zones: usize,
+    /// Relative request rate of clients in each zone, including backendless
+    /// zones. Defaults to equal demand everywhere.
+    #[arg(long, value_delimiter = ',')]
+    demand: Vec<f64>,
+    /// Requests per client zone, on average. [default: 100000]
+    #[arg(long)]
+    iterations: Option<u64>,
+    /// [default: 42]
+    #[arg(long)]
+    seed: Option<u64>,
+    /// [default: picker]
+    #[arg(long)]
+    strategy: Option<Strategy>,
+    /// Price cross-zone traffic at this many dollars per GB in each direction.
+    #[arg(long)]
+    cross_zone_price: Option<f64>,
+    /// Bytes per request, for pricing egress. [default: 1024]
+    #[arg(long)]
+    request_bytes: Option<f64>,
+    /// Bytes per response, for pricing egress. [default: 1024]
+    #[arg(long)]
+    response_bytes: Option<f64>,
+    /// Add this many milliseconds of round trip to cross-zone requests.
+    #[arg(long)]
+    cross_zone_rtt: Option<f64>,
+    /// Add exponentially distributed jitter with this mean, in
+    /// milliseconds, to every round trip.
+    #[arg(long)]
+    rtt_jitter: Option<f64>,
+}
+
+#[derive(Args, Clone)]
+struct DesArgs {
+    /// Offered load as a fraction of the fleet's total throughput.
+    #[arg(long, default_value_t = 0.8)]
+    utilization: f64,
+    /// Requests each backend serves concurrently; the rest queue.
+    #[arg(long, default_value_t = 1)]
+    concurrency: usize,
+    /// Requests per second one unit of capacity serves with every slot busy.
+    #[arg(long, default_value_t = 100.0)]
+    rate_per_capacity: f64,
+    /// Simulated seconds of arrivals.
+    #[arg(long, default_value_t = 60.0)]
+    duration: f64,
+    /// Simulated seconds to run before measuring.
+    #[arg(long, default_value_t = 5.0)]
+    warmup: f64,
+    /// Independent runs to make in parallel, with seeds derived from --seed,
+    /// whose latency histograms are merged.
+    #[arg(long, default_value_t = 1)]
+    runs: u64,
+}
+impl DesArgs {
+    fn config(&self) -> Result<DesConfig, String> {
+        if self.runs == 0 {
+            return Err("--runs must be at least 1".to_string());
+        }
+        let config = DesConfig {
+            rate_per_capacity: self.rate_per_capacity,
+            concurrency: self.concurrency,
+            utilization: self.utilization,
+            duration: self.duration,
+            warmup: self.warmup,
+            ..DesConfig::default()
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl ScenarioArgs {
+    fn zone(idx: usize) -> Zone {
+        Zone(b'a' + idx as u8)
+    }
+    fn build(&self) -> Result<Scenario, String> {
+        let mut scenario = match &self.scenario {
+            Some(path) => Scenario::load(path).map_err(|e| format!("{}: {e}", path.display()))?,
+            None => self.build_from_flags()?,
+        };
+        if let Some(iterations) = self.iterations {
+            scenario.iterations = iterations;
+        }
+        if let Some(seed) = self.seed {
+            scenario.seed = seed;
+        }
+        if let Some(strategy) = self.strategy {
+            scenario.strategy = strategy;
+        }
+        for (value, flag) in [
+            (self.cross_zone_price, "--cross-zone-price"),
+            (self.request_bytes, "--request-bytes"),
+            (self.response_bytes, "--response-bytes"),
+            (self.cross_zone_rtt, "--cross-zone-rtt"),
+            (self.rtt_jitter, "--rtt-jitter"),
+        ] {
+            if value.is_some_and(|v| !(v.is_finite() && v >= 0.0)) {
+                return Err(format!("{flag} must be finite and non-negative"));
+            }
+        }
+        if self.cross_zone_price.is_some()
+            || self.request_bytes.is_some()
+            || self.response_bytes.is_some()
+        {
+            let model = scenario.egress.get_or_insert_with(CostModel::default);
+            if let Some(price) = self.cross_zone_price {
+                model.cross_zone_per_gb = price;
+            }
+            if let Some(bytes) = self.request_bytes {
+                model.request_bytes = Distribution::Fixed(bytes);
+            }
+            if let Some(bytes) = self.response_bytes {
+                model.response_bytes = Distribution::Fixed(bytes);
+            }
+        }
+        if self.cross_zone_rtt.is_some() || self.rtt_jitter.is_some() {
+            let model = scenario.network.get_or_insert_with(RttModel::default);
+            if let Some(ms) = self.cross_zone_rtt {
+                model.cross_zone_ms = ms;
+            }
+            if let Some(mean) = self.rtt_jitter {
+                model.jitter_ms = Some(Distribution::Exponential { mean });
+            }
+        }
+        scenario.validate().map_err(|e| e.to_string())?;
+        Ok(scenario)
+    }
+    fn build_from_flags(&self) -> Result<Scenario, String> {
+        let client_zones = self.zones + self.backendless_zones;
+        if client_zones > 26 {
+            return Err("at most 26 zones are supported".to_string());
+        }
+        let counts = per_zone(&self.backends_per_zone, self.zones, "--backends-per-zone")?;
+        let capacities = per_zone(&self.capacity, self.zones, "--capacity")?;
+        let demand = if self.demand.is_empty() {
+            vec![1.0; client_zones]
+        } else {
+            per_zone(&self.demand, client_zones, "--demand")?
+        };
+        let zones = (0..client_zones)
+            .map(|i| {
+                let spec = ZoneSpec {
+                    backends: counts.get(i).copied().unwrap_or(0),
+                    capacity: capacities.get(i).copied().unwrap_or(1.0),
+                    clients: demand[i],
+                };
+                (Self::zone(i), spec)
+            })
+            .collect();
+        let scenario = Scenario {
+            name: None,
+            strategy: Strategy::Picker,
+            iterations: DEFAULT_ITERATIONS,
+            seed: DEFAULT_SEED,
+            zones,
+            backends: Vec::new(),
+            egress: None,
+            network: None,
+        };
+        scenario.validate().map_err(|e| e.to_string())?;
+        Ok(scenario)
+    }
+}
+
+/// Expand a per-zone flag, repeating a single value across every zone.
+fn per_zone<T: Copy>(values: &[T], zones: usize, flag: &str) -> Result<Vec<T>, String> {
+    match values {
+        [v] => Ok(vec![*v; zones]),
+        vs if vs.len() == zones => Ok(vs.to_vec()),
+        vs => Err(format!(
+            "{flag} has {} values, expected 1 or {zones}",
+            vs.len()
+        )),
+    }
+}
+
+/// Refuse strategies whose results would be misleading outside `des`.
+fn check_sampled(strategy: Strategy) -> Result<(), String> {
+    if strategy.needs_feedback() {
+        return Err(format!(
+            "strategy {strategy} steers by request completions; simulate it with `des`"
+        ));
+    }
+    Ok(())
+}
+
+fn run(args: &ScenarioArgs, format: Option<Format>) -> Result<(), String> {
+    let scenario = args.build()?;
+    check_sampled(scenario.strategy)?;
+    let report = scenario.run();
+    if let Some(format) = format {
+        let records = export::report_records(&report, &scenario.params());
+        return write_records(format, &records);
+    }
+    println!("strategy = {}", scenario.strategy);
+    println!("{report}");
+    Ok(())
+}
+
+fn write_records(format: Format, records: &[Record]) -> Result<(), String> {
+    format
+        .write(&mut std::io::stdout().lock(), records)
+        .map_err(|e| e.to_string())
+}
+
+fn sweep(
+    args: &ScenarioArgs,
+    zone: char,
+    from: usize,
+    to: usize,
+    step: usize,
+    format: Option<Format>,
+) -> Result<(), String> {
+    if from > to {
+        return Err(format!(
+            "--from {from} is past --to {to}, so nothing would run"
+        ));
+    }
+    let mut scenario = args.build()?;
+    check_sampled(scenario.strategy)?;
+    let zone = u8::try_from(zone)
+        .ok()
+        .map(Zone)
+        .filter(|z| scenario.zones.contains_key(z))
+        .ok_or_else(|| format!("--zone {zone} is not a zone in this scenario"))?;
+    let mut records = Vec::new();
+    if format.is_none() {
+        println!("strategy = {}", scenario.strategy);
+        println!("seed = {}", scenario.seed);
+        println!("backends\tmin_load\tmax_load\tin_zone");
+    }
+    for n in (from..=to).step_by(step) {
+        scenario.zones.get_mut(&zone).unwrap().backends = n;
+        scenario.validate().map_err(|e| e.to_string())?;
+        let report = scenario.run();
+        if format.is_some() {
+            let mut params = scenario.params();
+            params.push(("swept_zone".to_string(), Value::from(zone.to_string())));
+            params.push(("swept_backends".to_string(), Value::from(n as u64)));
+            records.extend(export::report_records(&report, &params));
+            continue;
+        }
+        let load = report.normalized_load();
+        let min_load = load.values().copied().fold(f64::INFINITY, f64::min);
+        let max_load = load.values().copied().fold(0.0, f64::max);
+        println!(
+            "{n}\t{min_load:.5}\t{max_load:.5}\t{:.5}",
+            report.in_zone_fraction()
+        );
+    }
+    match format {
+        Some(format) => write_records(format, &records),
+        None => Ok(()),
+    }
+}
+
+fn solve(args: &ScenarioArgs) -> Result<(), String> {
+    let scenario = args.build()?;
+    check_sampled(scenario.strategy)?;
+    println!("strategy = {}", scenario.strategy);
+    println!("{}", scenario.solve());
+    Ok(())
+}
+
+fn matrix(args: &ScenarioArgs, exact: bool) -> Result<(), String> {
+    let scenario = args.build()?;
+    check_sampled(scenario.strategy)?;
+    let flows = if exact {
+        scenario.solve().flows
+    } else {
+        scenario.run().flows
+    };
+    println!("strategy = {}", scenario.strategy);
+    if !exact {
+        println!("seed = {}", scenario.seed);
+    }
+    println!("{flows}");
+    Ok(())
+}
+
+fn des(args: &ScenarioArgs, des: &DesArgs) -> Result<(), String> {
+    let scenario = args.build()?;
+    let report = scenario.simulate_parallel(&des.config()?, des.runs);
+    println!("strategy = {}", scenario.strategy);
+    println!("{report}");
+    Ok(())
+}
+
+fn keyed(
+    args: &ScenarioArgs,
+    strategies: &[KeyedStrategy],
+    keys: u64,
+    churn: usize,
+    zone_aware: bool,
+) -> Result<(), String> {
+    if keys == 0 {
+        return Err("--keys must be at least 1".to_string());
+    }
+    if zone_aware && churn > 0 {
+        return Err("--churn is not supported with --zonal".to_string());
+    }
+    let scenario = args.build()?;
+    let backends = scenario.backends();
+    let demand = scenario.demand();
+    if !zone_aware {
+        println!("seed = {}", scenario.seed);
+    }
+    for &strategy in strategies {
+        println!("hash = {strategy}");
+        if zone_aware {
+            let report = keyed::zonal_strategy(strategy, &backends, &demand, keys, scenario.seed);
+            println!("{report}");
+        } else {
+            let report = keyed::spread_strategy(strategy, &backends, keys, scenario.seed);
+            println!("{report}");
+        }
+
+        // Grow and shrink the fleet by one backend like its last one.
+        let Some(last) = backends.last() else {
+            continue;
+        };
+        let total_capacity: f64 = backends.iter().map(|b| b.capacity).sum();
+        let added = Backend {
+            id: BackendId(backends.iter().map(|b| b.id.0).max().unwrap_or(0) + 1),
+            ..last.clone()
+        };
+        let grown: Vec<Backend> = backends.iter().cloned().chain([added]).collect();
+        let shrunk = &backends[..backends.len() - 1];
+        let moved = |after: &[Backend]| {
+            if zone_aware {
+                keyed::zonal_remap_fraction_strategy(
+                    strategy,
+                    &backends,
+                    after,
+                    &demand,
+                    keys,
+                    scenario.seed,
+                )
+            } else {
+                keyed::remap_fraction_strategy(strategy, &backends, after, keys, scenario.seed)
+            }
+        };
+        println!(
+            "remapped on add = {:.5} (ideal {:.5})",
+            moved(&grown),
+            last.capacity / (total_capacity + last.capacity)
+        );
+        println!(
+            "remapped on remove = {:.5} (ideal {:.5})",
+            moved(shrunk),
+            last.capacity / total_capacity
+        );
+        if churn > 0 {
+            let steps = keyed::churn_strategy(strategy, &backends, churn, keys, scenario.seed);
+            let remapped: f64 = steps.iter().map(|s| s.remapped).sum();
+            let minimal: f64 = steps.iter().map(|s| s.minimal).sum();
+            println!("remapped over {churn} changes = {remapped:.5} (ideal {minimal:.5})");
+        }
+    }
+    Ok(())
+}
+
+fn bounded(
+    args: &ScenarioArgs,
+    epsilons: &[f64],
+    keys: u64,
+    requests: u64,
+    zipf: f64,
+) -> Result<(), String> {
+    if keys == 0 {
+        return Err("--keys must be at least 1".to_string());
+    }
+    if requests == 0 {
+        return Err("--requests must be at least 1".to_string());
+    }
+    if !zipf.is_finite() {
+        return Err("--zipf must be finite".to_string());
+    }
+    if epsilons.iter().any(|&e| !e.is_finite() || e <= 0.0) {
+        return Err("--epsilon values must be finite and positive".to_string());
+    }
+    let scenario = args.build()?;
+    let workload = keyed::Zipf::new(keys, zipf);
+    let points = keyed::bounded_tradeoff(
+        &scenario.backends(),
+        epsilons,
+        &workload,
+        requests,
+        scenario.seed,
+    );
+    println!("seed = {}", scenario.seed);
+    println!("epsilon\taffinity\tmax_load");
+    for p in points {
+        println!("{}\t{:.5}\t{:.5}", p.epsilon, p.affinity, p.max_load);
+    }
+    Ok(())
+}
+
+fn window(
+    args: &ScenarioArgs,
+    strategies: &[Strategy],
+    window: u64,
+    windows: u64,
+) -> Result<(), String> {
+    if window == 0 || windows == 0 {
+        return Err("--window and --windows must be at least 1".to_string());
+    }
+    for &strategy in strategies {
+        check_sampled(strategy)?;
+    }
+    let scenario = args.build()?;
+    let backends = scenario.backends();
+    let demand = scenario.demand();
+    if window < demand.len() as u64 {
+        return Err(format!(
+            "--window must be at least the number of client zones ({})",
+            demand.len()
+        ));
+    }
+    println!("seed = {}", scenario.seed);
+    for &strategy in strategies {
+        let spread = sim::window_spread_strategy(
+            strategy,
+            &backends,
+            &demand,
+            window,
+            windows,
+            scenario.seed,
+        );
+        println!("{strategy}: {spread}");
+    }
+    Ok(())
+}
+
+fn dump(args: &ScenarioArgs) -> Result<(), String> {
+    println!("{}", args.build()?.to_json());
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match &cli.command {
+        Command::Run { scenario, format } => run(scenario, *format),
+        Command::Sweep {
+            scenario,
+            zone,
+            from,
+            to,
+            step,
+            format,
+        } => sweep(scenario, *zone, *from, *to, *step, *format),
+        Command::Solve(args) => solve(args),
+        Command::Matrix { scenario, exact } => matrix(scenario, *exact),
+        Command::Des { scenario, des: d } => des(scenario, d),
+        Command::Keyed {
+            scenario,
+            hash,
+            keys,
+            churn,
+            zonal,
+        } => keyed(scenario, hash, *keys, *churn, *zonal),
+        Command::Bounded {
+            scenario,
+            epsilon,
+            keys,
+            requests,
+            zipf,
+        } => bounded(scenario, epsilon, *keys, *requests, *zipf),
+        Command::Window {
+            scenario,
+            compare,
+            window: w,
+            windows,
+        } => window(scenario, compare, *w, *windows),
+        Command::Dump(args) => dump(args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
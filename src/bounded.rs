@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+
+use crate::{Backend, BackendId, KeyedBalancer, PickerError, RingHash};
+
+/// Consistent hashing with bounded loads (Mirrokni, Thorup and Zadimoghaddam).
+///
+/// Keys walk a [`RingHash`] from their usual position and take the first
+/// backend whose load is below `(1 + epsilon)` times its fair share of the
+/// current total, counting the request being placed. A key only leaves its
+/// home backend when that backend is full, so a small `epsilon` caps the
+/// maximum load tightly at the cost of cache affinity, and a large one
+/// approaches the plain ring.
+#[derive(Clone, Debug)]
+pub struct BoundedLoad {
+    ring: RingHash,
+    epsilon: f64,
+    capacity: BTreeMap<BackendId, f64>,
+    total_capacity: f64,
+    load: BTreeMap<BackendId, u64>,
+    total: u64,
+}
+impl BoundedLoad {
+    /// The headroom used by [`KeyedBalancer::from_backends`].
+    pub const DEFAULT_EPSILON: f64 = 0.25;
+
+    /// # Panics
+    ///
+    /// If `epsilon` is rejected by [`BoundedLoad::try_with_epsilon`].
+    pub fn with_epsilon(backends: &[Backend], epsilon: f64) -> Self {
+        match Self::try_with_epsilon(backends, epsilon) {
+            Ok(lb) => lb,
+            Err(e) => panic!("{e}"),
+        }
+    }
+    /// Like [`BoundedLoad::with_epsilon`], but rejecting an `epsilon` that is
+    /// NaN, infinite or not positive.
+    pub fn try_with_epsilon(backends: &[Backend], epsilon: f64) -> Result<Self, PickerError> {
+        if !epsilon.is_finite() || epsilon <= 0.0 {
+            return Err(PickerError::InvalidEpsilon { epsilon });
+        }
+        let capacity: BTreeMap<BackendId, f64> = backends
+            .iter()
+            .filter(|b| b.capacity > 0.0)
+            .map(|b| (b.id, b.capacity))
+            .collect();
+        Ok(Self {
+            ring: RingHash::with_vnodes(backends, RingHash::DEFAULT_VNODES),
+            epsilon,
+            total_capacity: capacity.values().sum(),
+            load: capacity.keys().map(|&id| (id, 0)).collect(),
+            capacity,
+            total: 0,
+        })
+    }
+    pub fn epsilon(&self) -> f64 {
+        self.epsilon
+    }
+    /// The backend `key` would go to on an unloaded ring.
+    pub fn home(&self, key: &[u8]) -> Option<BackendId> {
+        self.ring.walk(key).next()
+    }
+    /// The requests currently placed on `backend`.
+    pub fn load(&self, backend: BackendId) -> u64 {
+        self.load.get(&backend).copied().unwrap_or_default()
+    }
+    /// Release a request placed on `backend` once it completes.
+    pub fn finish(&mut self, backend: BackendId) {
+        if let Some(load) = self.load.get_mut(&backend).filter(|l| **l > 0) {
+            *load -= 1;
+            self.total -= 1;
+        }
+    }
+    fn bound(&self, backend: BackendId) -> f64 {
+        let share = self.capacity[&backend] / self.total_capacity;
+        ((1.0 + self.epsilon) * (self.total + 1) as f64 * share).ceil()
+    }
+}
+impl KeyedBalancer for BoundedLoad {
+    fn from_backends(backends: Vec<Backend>) -> Self {
+        Self::with_epsilon(&backends, Self::DEFAULT_EPSILON)
+    }
+    /// Place a request for `key`. It counts towards the backend's load until
+    /// [`BoundedLoad::finish`] is called.
+    fn sample_key(&mut self, key: &[u8]) -> Option<BackendId> {
+        // The bounds sum to more than the current total, so some backend
+        // always has room and the walk ends within one lap.
+        let id = self
+            .ring
+            .walk(key)
+            .find(|&id| (self.load[&id] as f64) < self.bound(id))?;
+        *self.load.get_mut(&id).unwrap() += 1;
+        self.total += 1;
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{keyed, sim, Zone};
+
+    #[test]
+    fn never_exceeds_the_bound() {
+        let backends = sim::topology(&[(Zone(b'a'), 4, 1.0), (Zone(b'b'), 4, 2.0)]);
+        let mut lb = BoundedLoad::with_epsilon(&backends, 0.1);
+        // Every request for one hot key still spreads once its home fills up.
+        for i in 0..12_000u64 {
+            let key = if i % 2 == 0 {
+                *b"hot-key!"
+            } else {
+                keyed::key(1, i)
+            };
+            lb.sample_key(&key).unwrap();
+            for b in &backends {
+                assert!(lb.load(b.id) as f64 <= lb.bound(b.id), "{:?}", b.id);
+            }
+        }
+        let home = lb.home(b"hot-key!").unwrap();
+        let fair = 12_000.0 * backends[home.0 as usize].capacity / 12.0;
+        assert!(lb.load(home) as f64 <= 1.1 * fair + 1.0);
+        lb.finish(home);
+        assert_eq!(lb.total, 11_999);
+    }
+
+    #[test]
+    fn rejects_headroom_that_would_strand_keys() {
+        let backends = sim::topology(&[(Zone(b'a'), 2, 1.0)]);
+        for epsilon in [-0.9, 0.0, f64::INFINITY] {
+            assert_eq!(
+                BoundedLoad::try_with_epsilon(&backends, epsilon).err(),
+                Some(PickerError::InvalidEpsilon { epsilon })
+            );
+        }
+        assert!(BoundedLoad::try_with_epsilon(&backends, f64::NAN).is_err());
+    }
+}
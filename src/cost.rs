@@ -0,0 +1,123 @@
+//! Cross-zone egress cost accounting.
+
+use std::collections::BTreeMap;
+
+use crate::{distribution::Distribution, matrix::TrafficMatrix, Backend, Zone};
+
+const BYTES_PER_GB: f64 = 1e9;
+
+/// What it costs to move bytes between zones.
+///
+/// Requests are billed at the price from the client's zone to the backend's,
+/// and responses at the price back again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CostModel {
+    /// Dollars per GB between two different zones, unless overridden in `pairs`.
+    pub cross_zone_per_gb: f64,
+    /// Dollars per GB within a zone, unless overridden in `pairs`.
+    pub same_zone_per_gb: f64,
+    /// Dollars per GB from one zone to another.
+    pub pairs: BTreeMap<(Zone, Zone), f64>,
+    pub request_bytes: Distribution,
+    pub response_bytes: Distribution,
+}
+impl Default for CostModel {
+    /// AWS-style pricing: $0.01/GB in each direction across zones, free within a zone.
+    fn default() -> Self {
+        Self {
+            cross_zone_per_gb: 0.01,
+            same_zone_per_gb: 0.0,
+            pairs: BTreeMap::new(),
+            request_bytes: Distribution::Fixed(1024.0),
+            response_bytes: Distribution::Fixed(1024.0),
+        }
+    }
+}
+impl CostModel {
+    /// Dollars per GB sent from `from` to `to`.
+    pub fn price(&self, from: Zone, to: Zone) -> f64 {
+        match self.pairs.get(&(from, to)) {
+            Some(&price) => price,
+            None if from == to => self.same_zone_per_gb,
+            None => self.cross_zone_per_gb,
+        }
+    }
+    /// The expected dollar cost of one request, and its response, from `client` to `backend`.
+    pub fn request_cost(&self, client: Zone, backend: Zone) -> f64 {
+        (self.request_bytes.mean() * self.price(client, backend)
+            + self.response_bytes.mean() * self.price(backend, client))
+            / BYTES_PER_GB
+    }
+    /// The expected egress bill for `flows`, whose entries are request counts.
+    pub fn egress(&self, flows: &TrafficMatrix) -> EgressCost {
+        let mut cost = EgressCost::default();
+        for ((client, backend), requests) in flows.iter() {
+            let dollars = requests * self.request_cost(client, backend);
+            if client != backend {
+                cost.cross_zone_bytes +=
+                    requests * (self.request_bytes.mean() + self.response_bytes.mean());
+            }
+            cost.per_pair.insert((client, backend), dollars);
+            cost.total += dollars;
+        }
+        cost
+    }
+}
+
+/// Egress dollars, in total and per `(client zone, backend zone)` pair.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EgressCost {
+    pub per_pair: BTreeMap<(Zone, Zone), f64>,
+    pub total: f64,
+    pub cross_zone_bytes: f64,
+}
+
+/// How `requests` would flow if clients ignored zones entirely and sent
+/// traffic to each backend in proportion to its capacity. This is the
+/// baseline that zonal affinity saves money against.
+pub fn zone_blind_flows(
+    backends: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+    requests: f64,
+) -> TrafficMatrix {
+    let total_capacity: f64 = backends.iter().map(|b| b.capacity).sum();
+    let total_demand: f64 = demand.values().sum();
+    let mut flows = TrafficMatrix::new();
+    for (&client, &rate) in demand {
+        for b in backends {
+            let share = rate / total_demand * b.capacity / total_capacity;
+            flows.add(client, b.zone, requests * share);
+        }
+    }
+    flows
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sim;
+
+    #[test]
+    fn prices_cross_zone_requests_in_both_directions() {
+        let model = CostModel {
+            pairs: [((Zone(b'a'), Zone(b'b')), 0.02)].into_iter().collect(),
+            request_bytes: Distribution::Fixed(1e6),
+            response_bytes: Distribution::Fixed(4e6),
+            ..CostModel::default()
+        };
+        let mut flows = TrafficMatrix::new();
+        flows.add(Zone(b'a'), Zone(b'a'), 1000.0);
+        flows.add(Zone(b'a'), Zone(b'b'), 1000.0);
+        let cost = model.egress(&flows);
+        // 1 GB of requests at $0.02 plus 4 GB of responses at $0.01.
+        assert!((cost.total - 0.06).abs() < 1e-12, "{cost:?}");
+        assert_eq!(cost.per_pair[&(Zone(b'a'), Zone(b'a'))], 0.0);
+        assert_eq!(cost.cross_zone_bytes, 5e9);
+
+        let backends = sim::topology(&[(Zone(b'a'), 1, 1.0), (Zone(b'b'), 3, 1.0)]);
+        let demand = [(Zone(b'a'), 1.0), (Zone(b'b'), 1.0)].into_iter().collect();
+        let blind = zone_blind_flows(&backends, &demand, 800.0);
+        assert_eq!(blind.get(Zone(b'a'), Zone(b'b')), 300.0);
+        assert_eq!(blind.in_zone_fraction(), 0.5);
+    }
+}
@@ -0,0 +1,642 @@
+//! Discrete-event simulation of clients, pickers and queueing backends.
+//!
+//! Clients in each zone generate requests as a Poisson process, route each one
+//! with their own [`LoadBalancer`], and backends serve them with a fixed number
+//! of concurrency slots and an unbounded FIFO queue. Service times are
+//! exponential, with each backend's throughput proportional to its capacity.
+//! An optional [`RttModel`] adds the network round trip between the client's
+//! zone and the backend's to each request's latency.
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BinaryHeap, VecDeque},
+    fmt,
+};
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::{
+    histogram::Histogram,
+    network::RttModel,
+    sim::{self, Client, Strategy},
+    Backend, BackendId, LoadBalancer, Zone,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DesConfig {
+    /// Requests per second one unit of capacity serves when all slots are busy.
+    pub rate_per_capacity: f64,
+    /// Requests each backend serves at once; the rest wait in its queue.
+    pub concurrency: usize,
+    /// Offered load as a fraction of the whole fleet's throughput.
+    pub utilization: f64,
+    /// Simulated seconds of arrivals.
+    pub duration: f64,
+    /// Requests that arrive before this many seconds are not measured.
+    pub warmup: f64,
+    pub seed: u64,
+    /// Network round trips added to each request, if any.
+    pub network: Option<RttModel>,
+}
+impl Default for DesConfig {
+    fn default() -> Self {
+        Self {
+            rate_per_capacity: 100.0,
+            concurrency: 1,
+            utilization: 0.8,
+            duration: 60.0,
+            warmup: 5.0,
+            seed: crate::DEFAULT_SEED,
+            network: None,
+        }
+    }
+}
+impl DesConfig {
+    /// Check that the run would terminate and produce finite results.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.utilization.is_finite() && self.utilization > 0.0) {
+            return Err(format!(
+                "utilization must be finite and positive, got {}",
+                self.utilization
+            ));
+        }
+        if !(self.rate_per_capacity.is_finite() && self.rate_per_capacity > 0.0) {
+            return Err(format!(
+                "rate_per_capacity must be finite and positive, got {}",
+                self.rate_per_capacity
+            ));
+        }
+        if self.concurrency == 0 {
+            return Err("concurrency must be at least 1".to_string());
+        }
+        if !(self.duration.is_finite() && self.warmup >= 0.0 && self.warmup < self.duration) {
+            return Err("warmup must be in [0, duration) and duration finite".to_string());
+        }
+        if let Some(network) = &self.network {
+            network.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// What one backend did during the measured part of the run.
+#[derive(Clone, Debug, Default)]
+pub struct BackendStats {
+    pub served: u64,
+    /// Slot-seconds spent serving requests.
+    pub busy_time: f64,
+    pub total_queueing: f64,
+    pub max_queue: usize,
+    /// End-to-end latency of the requests it served, in microseconds.
+    pub latency: Histogram,
+}
+impl BackendStats {
+    fn merge(&mut self, other: &BackendStats) {
+        self.served += other.served;
+        self.busy_time += other.busy_time;
+        self.total_queueing += other.total_queueing;
+        self.max_queue = self.max_queue.max(other.max_queue);
+        self.latency.merge(&other.latency);
+    }
+}
+
+/// What clients in one zone saw during the measured part of the run.
+#[derive(Clone, Debug, Default)]
+pub struct ClientStats {
+    pub completed: u64,
+    pub in_zone: u64,
+    /// End-to-end latency of their requests, in microseconds.
+    pub latency: Histogram,
+}
+impl ClientStats {
+    fn merge(&mut self, other: &ClientStats) {
+        self.completed += other.completed;
+        self.in_zone += other.in_zone;
+        self.latency.merge(&other.latency);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DesReport {
+    pub config: DesConfig,
+    /// How many independent runs were merged into this report.
+    pub runs: u64,
+    pub backends: BTreeMap<BackendId, Backend>,
+    pub backend_stats: BTreeMap<BackendId, BackendStats>,
+    pub client_stats: BTreeMap<Zone, ClientStats>,
+    /// Requests the client could not route anywhere.
+    pub unrouted: u64,
+}
+impl DesReport {
+    /// The measured window, in simulated seconds.
+    pub fn window(&self) -> f64 {
+        self.config.duration - self.config.warmup
+    }
+    /// Combine the results of another run of the same topology, e.g. one
+    /// with a different seed run in parallel.
+    pub fn merge(&mut self, other: &DesReport) {
+        self.runs += other.runs;
+        self.unrouted += other.unrouted;
+        for (id, stats) in &other.backend_stats {
+            self.backend_stats.entry(*id).or_default().merge(stats);
+        }
+        for (zone, stats) in &other.client_stats {
+            self.client_stats.entry(*zone).or_default().merge(stats);
+        }
+    }
+    /// The fraction of each backend's slots that were busy during the window.
+    pub fn utilization(&self) -> BTreeMap<BackendId, f64> {
+        let window = self.window() * self.config.concurrency as f64 * self.runs as f64;
+        self.backend_stats
+            .iter()
+            .map(|(&id, s)| (id, s.busy_time / window))
+            .collect()
+    }
+    /// Mean time spent queued before service, in seconds.
+    pub fn mean_queueing(&self) -> f64 {
+        let (total, n) = self
+            .backend_stats
+            .values()
+            .fold((0.0, 0), |(t, n), s| (t + s.total_queueing, n + s.served));
+        total / n as f64
+    }
+    /// Mean end-to-end latency, in seconds.
+    pub fn mean_latency(&self) -> f64 {
+        self.latency().mean() / 1e6
+    }
+    /// Latency of every measured request, in microseconds.
+    pub fn latency(&self) -> Histogram {
+        let mut all = Histogram::new();
+        for s in self.client_stats.values() {
+            all.merge(&s.latency);
+        }
+        all
+    }
+    /// Latency of requests served by backends in each zone, in microseconds.
+    pub fn zone_latency(&self) -> BTreeMap<Zone, Histogram> {
+        let mut acc: BTreeMap<Zone, Histogram> = BTreeMap::new();
+        for (id, s) in &self.backend_stats {
+            acc.entry(self.backends[id].zone)
+                .or_default()
+                .merge(&s.latency);
+        }
+        acc
+    }
+    pub fn in_zone_fraction(&self) -> f64 {
+        let (in_zone, n) = self
+            .client_stats
+            .values()
+            .fold((0, 0), |(i, n), s| (i + s.in_zone, n + s.completed));
+        in_zone as f64 / n as f64
+    }
+}
+impl fmt::Display for DesReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "seed = {}", self.config.seed)?;
+        writeln!(f, "latencies in microseconds")?;
+        let utilization = self.utilization();
+        for (id, s) in &self.backend_stats {
+            writeln!(
+                f,
+                "[{}] util = {:.3}  wait = {:.3}ms  max queue = {}  {}",
+                self.backends[id].zone,
+                utilization[id],
+                1e3 * s.total_queueing / s.served.max(1) as f64,
+                s.max_queue,
+                s.latency,
+            )?;
+        }
+        for (zone, h) in self.zone_latency() {
+            writeln!(f, "backends in {zone}: {h}")?;
+        }
+        for (zone, s) in &self.client_stats {
+            writeln!(
+                f,
+                "clients in {zone}: in-zone = {:.5}  {}",
+                s.in_zone as f64 / s.completed.max(1) as f64,
+                s.latency,
+            )?;
+        }
+        writeln!(f, "all requests: {}", self.latency())?;
+        writeln!(f, "mean wait = {:.3}ms", 1e3 * self.mean_queueing())?;
+        writeln!(f, "mean latency = {:.3}ms", 1e3 * self.mean_latency())?;
+        write!(f, "% in-zone = {}", self.in_zone_fraction())
+    }
+}
+
+struct Event {
+    time: f64,
+    // Breaks ties so that simultaneous events run in the order they were scheduled.
+    seq: u64,
+    kind: EventKind,
+}
+enum EventKind {
+    Arrival { client: usize },
+    Completion { backend: usize, request: Request },
+}
+impl PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Event {}
+impl PartialOrd for Event {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Event {
+    // Reversed, so the max-heap pops the earliest event first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .time
+            .total_cmp(&self.time)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Request {
+    client: usize,
+    arrival: f64,
+    start: f64,
+    // Seconds of network round trip. Requests are enqueued as soon as they
+    // arrive, and the whole round trip is added when they complete; delaying
+    // Poisson arrivals by independent amounts leaves them Poisson.
+    rtt: f64,
+}
+
+struct BackendState {
+    busy: usize,
+    queue: VecDeque<Request>,
+    // Mean service time with all slots busy is `concurrency / (capacity * rate_per_capacity)`.
+    mean_service: f64,
+}
+
+struct Engine {
+    now: f64,
+    seq: u64,
+    events: BinaryHeap<Event>,
+    prng: SmallRng,
+}
+impl Engine {
+    fn schedule(&mut self, time: f64, kind: EventKind) {
+        self.seq += 1;
+        self.events.push(Event {
+            time,
+            seq: self.seq,
+            kind,
+        });
+    }
+    fn exponential(&mut self, mean: f64) -> f64 {
+        -mean * (1.0 - self.prng.gen::<f64>()).ln()
+    }
+}
+
+/// Simulate a client per zone in `demand`, each routing with its own `L`.
+///
+/// # Panics
+///
+/// If `config` fails [`DesConfig::validate`], since the run would otherwise
+/// never end or report NaN.
+pub fn run<L: LoadBalancer>(
+    backends: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+    config: &DesConfig,
+) -> DesReport {
+    if let Err(e) = config.validate() {
+        panic!("invalid DES config: {e}");
+    }
+    let mut fleet: Vec<Client<L>> = sim::fleet(backends, demand, config.seed);
+    let index: BTreeMap<BackendId, usize> = backends
+        .iter()
+        .enumerate()
+        .map(|(i, b)| (b.id, i))
+        .collect();
+    let mut states: Vec<BackendState> = backends
+        .iter()
+        .map(|b| BackendState {
+            busy: 0,
+            queue: VecDeque::new(),
+            mean_service: config.concurrency as f64 / (b.capacity * config.rate_per_capacity),
+        })
+        .collect();
+
+    let total_capacity: f64 = backends.iter().map(|b| b.capacity).sum();
+    let total_rate = config.utilization * total_capacity * config.rate_per_capacity;
+    let total_demand: f64 = demand.values().sum();
+    let arrival_means: Vec<f64> = fleet
+        .iter()
+        .map(|c| total_demand / (total_rate * c.rate))
+        .collect();
+
+    let mut report = DesReport {
+        config: config.clone(),
+        runs: 1,
+        backends: backends.iter().map(|b| (b.id, b.clone())).collect(),
+        backend_stats: backends
+            .iter()
+            .map(|b| (b.id, BackendStats::default()))
+            .collect(),
+        client_stats: fleet
+            .iter()
+            .map(|c| (c.zone, ClientStats::default()))
+            .collect(),
+        unrouted: 0,
+    };
+
+    let mut engine = Engine {
+        now: 0.0,
+        seq: 0,
+        events: BinaryHeap::new(),
+        // Keep the engine's stream independent of the per-client streams.
+        prng: SmallRng::seed_from_u64(sim::derive_seed(config.seed, u64::MAX)),
+    };
+    for (client, &mean) in arrival_means.iter().enumerate() {
+        let t = engine.exponential(mean);
+        engine.schedule(t, EventKind::Arrival { client });
+    }
+
+    while let Some(event) = engine.events.pop() {
+        engine.now = event.time;
+        match event.kind {
+            EventKind::Arrival { client } => {
+                if engine.now >= config.duration {
+                    continue;
+                }
+                let next = engine.now + engine.exponential(arrival_means[client]);
+                engine.schedule(next, EventKind::Arrival { client });
+                let Some(id) = fleet[client].lb.sample() else {
+                    if engine.now >= config.warmup {
+                        report.unrouted += 1;
+                    }
+                    continue;
+                };
+                fleet[client].lb.on_dispatch(id, engine.now);
+                let backend = index[&id];
+                let rtt = match &config.network {
+                    Some(model) => {
+                        let (from, to) = (fleet[client].zone, backends[backend].zone);
+                        model.sample(from, to, &mut engine.prng) / 1e3
+                    }
+                    None => 0.0,
+                };
+                let request = Request {
+                    client,
+                    arrival: engine.now,
+                    start: engine.now,
+                    rtt,
+                };
+                let state = &mut states[backend];
+                if state.busy < config.concurrency {
+                    state.busy += 1;
+                    let done = engine.now + engine.exponential(state.mean_service);
+                    engine.schedule(done, EventKind::Completion { backend, request });
+                } else {
+                    state.queue.push_back(request);
+                    let stats = report.backend_stats.get_mut(&id).unwrap();
+                    stats.max_queue = stats.max_queue.max(state.queue.len());
+                }
+            }
+            EventKind::Completion { backend, request } => {
+                let b = &backends[backend];
+                let state = &mut states[backend];
+                state.busy -= 1;
+                if let Some(mut next) = state.queue.pop_front() {
+                    next.start = engine.now;
+                    state.busy += 1;
+                    let done = engine.now + engine.exponential(state.mean_service);
+                    engine.schedule(
+                        done,
+                        EventKind::Completion {
+                            backend,
+                            request: next,
+                        },
+                    );
+                }
+                let utilization =
+                    (state.busy + state.queue.len()) as f64 / config.concurrency as f64;
+                fleet[request.client].lb.on_complete(
+                    b.id,
+                    engine.now,
+                    engine.now - request.arrival + request.rtt,
+                    Some(utilization),
+                );
+                // Count busy time inside the measured window only.
+                let stats = report.backend_stats.get_mut(&b.id).unwrap();
+                let busy_from = request.start.max(config.warmup);
+                let busy_to = engine.now.min(config.duration);
+                stats.busy_time += (busy_to - busy_from).max(0.0);
+                if request.arrival < config.warmup {
+                    continue;
+                }
+                let latency = micros(engine.now - request.arrival + request.rtt);
+                stats.served += 1;
+                stats.total_queueing += request.start - request.arrival;
+                stats.latency.record(latency);
+                let client_zone = fleet[request.client].zone;
+                let client = report.client_stats.get_mut(&client_zone).unwrap();
+                client.completed += 1;
+                client.latency.record(latency);
+                if b.zone == client_zone {
+                    client.in_zone += 1;
+                }
+            }
+        }
+    }
+    report
+}
+
+fn micros(seconds: f64) -> u64 {
+    (seconds * 1e6).round() as u64
+}
+
+/// [`run`] for a strategy chosen at runtime.
+pub fn run_strategy(
+    strategy: Strategy,
+    backends: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+    config: &DesConfig,
+) -> DesReport {
+    sim::with_strategy!(strategy, L => run::<L>(backends, demand, config))
+}
+
+/// Run `runs` independent copies of the simulation, with seeds derived from
+/// `config.seed`, and merge their results. The runs are split into contiguous
+/// chunks across at most one thread per available CPU, and merged in run
+/// order, so the result does not depend on how many threads there were.
+pub fn run_parallel(
+    strategy: Strategy,
+    backends: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+    config: &DesConfig,
+    runs: u64,
+) -> DesReport {
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |n| n.get() as u64)
+        .clamp(1, runs.max(1));
+    let chunk = runs.div_ceil(workers);
+    let reports: Vec<DesReport> = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..workers)
+            .map(|w| {
+                let indices = w * chunk..((w + 1) * chunk).min(runs);
+                s.spawn(move || {
+                    indices
+                        .map(|i| {
+                            let config = DesConfig {
+                                seed: sim::derive_seed(config.seed, i),
+                                ..config.clone()
+                            };
+                            run_strategy(strategy, backends, demand, &config)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    });
+    let mut reports = reports.into_iter();
+    let mut merged = reports.next().expect("at least one run");
+    for r in reports {
+        merged.merge(&r);
+    }
+    // Report the master seed the runs were derived from.
+    merged.config.seed = config.seed;
+    merged
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Picker;
+
+    #[test]
+    fn single_backend_matches_mm1() {
+        // One backend serving 100 req/s at 50% utilization is an M/M/1 queue:
+        // mean latency = 1 / (mu - lambda) = 20ms, mean wait = rho / (mu - lambda) = 10ms.
+        let backends = sim::topology(&[(Zone(b'a'), 1, 1.0)]);
+        let demand = [(Zone(b'a'), 1.0)].into_iter().collect();
+        let config = DesConfig {
+            utilization: 0.5,
+            duration: 2_000.0,
+            warmup: 10.0,
+            ..DesConfig::default()
+        };
+        let report = run::<Picker>(&backends, &demand, &config);
+        let latency = report.mean_latency();
+        let wait = report.mean_queueing();
+        assert!((latency - 0.020).abs() < 0.001, "latency = {latency}");
+        assert!((wait - 0.010).abs() < 0.001, "wait = {wait}");
+        let util = report.utilization()[&BackendId(0)];
+        assert!((util - 0.5).abs() < 0.02, "utilization = {util}");
+        // M/M/1 latency is exponential with mean 20ms, so p99 = 20ms * ln(100).
+        let p99 = report.latency().percentile(99.0) as f64 / 1e6;
+        assert!((p99 - 0.020 * 100f64.ln()).abs() < 0.005, "p99 = {p99}");
+    }
+
+    #[test]
+    fn rejects_configs_that_never_finish() {
+        let bad = [
+            DesConfig {
+                utilization: -0.5,
+                ..DesConfig::default()
+            },
+            DesConfig {
+                utilization: f64::NAN,
+                ..DesConfig::default()
+            },
+            DesConfig {
+                rate_per_capacity: 0.0,
+                ..DesConfig::default()
+            },
+        ];
+        for config in bad {
+            assert!(config.validate().is_err(), "{config:?}");
+        }
+        assert_eq!(DesConfig::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn cross_zone_requests_pay_the_round_trip() {
+        // Zone A spills most of its traffic into B. Without jitter the network
+        // model draws no random numbers, so both runs see the same requests.
+        let backends = sim::topology(&[(Zone(b'a'), 1, 1.0), (Zone(b'b'), 3, 1.0)]);
+        let demand = [(Zone(b'a'), 1.0), (Zone(b'b'), 1.0)].into_iter().collect();
+        let config = DesConfig {
+            duration: 20.0,
+            ..DesConfig::default()
+        };
+        let local = run::<Picker>(&backends, &demand, &config);
+        let network = DesConfig {
+            network: Some(RttModel {
+                cross_zone_ms: 2.0,
+                ..RttModel::default()
+            }),
+            ..config
+        };
+        let remote = run::<Picker>(&backends, &demand, &network);
+        assert_eq!(local.in_zone_fraction(), remote.in_zone_fraction());
+        let added = remote.mean_latency() - local.mean_latency();
+        let expected = 0.002 * (1.0 - local.in_zone_fraction());
+        assert!((added - expected).abs() < 1e-6, "{added} vs {expected}");
+        let b = &remote.client_stats[&Zone(b'b')];
+        assert_eq!(b.latency, local.client_stats[&Zone(b'b')].latency);
+    }
+
+    #[test]
+    fn parallel_runs_merge() {
+        let backends = sim::topology(&[(Zone(b'a'), 2, 1.0), (Zone(b'b'), 2, 1.0)]);
+        let demand = [(Zone(b'a'), 1.0), (Zone(b'b'), 1.0)].into_iter().collect();
+        let config = DesConfig {
+            duration: 30.0,
+            ..DesConfig::default()
+        };
+        let single = run_strategy(Strategy::Picker, &backends, &demand, &config);
+        let merged = run_parallel(Strategy::Picker, &backends, &demand, &config, 4);
+        assert_eq!(merged.runs, 4);
+        let (one, four) = (single.latency().count(), merged.latency().count());
+        assert!(four > 3 * one && four < 5 * one, "{one} vs {four}");
+        for (id, util) in merged.utilization() {
+            assert!((util - 0.8).abs() < 0.05, "{id:?}: utilization = {util}");
+        }
+
+        // More runs than threads are shared out, and every one of them is merged.
+        let short = DesConfig {
+            duration: 1.0,
+            warmup: 0.0,
+            ..DesConfig::default()
+        };
+        let cpus = std::thread::available_parallelism().map_or(1, |n| n.get() as u64);
+        let runs = 2 * cpus + 1;
+        let many = run_parallel(Strategy::Picker, &backends, &demand, &short, runs);
+        assert_eq!(many.runs, runs);
+        let again = run_parallel(Strategy::Picker, &backends, &demand, &short, runs);
+        assert_eq!(many.latency().count(), again.latency().count());
+    }
+
+    #[test]
+    fn zonal_picker_keeps_utilization_even() {
+        let backends = sim::topology(&[
+            (Zone(b'a'), 1, 1.0),
+            (Zone(b'b'), 5, 1.0),
+            (Zone(b'c'), 9, 1.0),
+        ]);
+        let demand = [(Zone(b'a'), 1.0), (Zone(b'b'), 1.0), (Zone(b'c'), 1.0)]
+            .into_iter()
+            .collect();
+        let config = DesConfig {
+            concurrency: 4,
+            duration: 300.0,
+            ..DesConfig::default()
+        };
+        let report = run::<Picker>(&backends, &demand, &config);
+        for (id, util) in report.utilization() {
+            assert!((util - 0.8).abs() < 0.05, "{id:?}: utilization = {util}");
+        }
+        assert!((report.in_zone_fraction() - 11.0 / 15.0).abs() < 0.01);
+        assert_eq!(report.unrouted, 0);
+    }
+}
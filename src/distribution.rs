@@ -0,0 +1,92 @@
+//! Distributions of payload sizes and network delays.
+
+use rand::Rng;
+
+/// A distribution of non-negative values, such as the bytes in a request body
+/// or the milliseconds of jitter on a round trip.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Distribution {
+    Fixed(f64),
+    Uniform {
+        min: f64,
+        max: f64,
+    },
+    Exponential {
+        mean: f64,
+    },
+    /// `exp(N(mu, sigma^2))`, the usual shape of payload sizes.
+    LogNormal {
+        mu: f64,
+        sigma: f64,
+    },
+}
+impl Distribution {
+    pub fn mean(&self) -> f64 {
+        match *self {
+            Distribution::Fixed(x) => x,
+            Distribution::Uniform { min, max } => (min + max) / 2.0,
+            Distribution::Exponential { mean } => mean,
+            Distribution::LogNormal { mu, sigma } => (mu + sigma * sigma / 2.0).exp(),
+        }
+    }
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        match *self {
+            Distribution::Fixed(x) => x,
+            Distribution::Uniform { min, max } => min + (max - min) * rng.gen::<f64>(),
+            Distribution::Exponential { mean } => -mean * (1.0 - rng.gen::<f64>()).ln(),
+            Distribution::LogNormal { mu, sigma } => (mu + sigma * standard_normal(rng)).exp(),
+        }
+    }
+    /// Check that the parameters are finite and no sample can be negative.
+    pub fn validate(&self) -> Result<(), String> {
+        let valid = match *self {
+            Distribution::Fixed(x) => x.is_finite() && x >= 0.0,
+            Distribution::Uniform { min, max } => {
+                min.is_finite() && max.is_finite() && 0.0 <= min && min <= max
+            }
+            Distribution::Exponential { mean } => mean.is_finite() && mean >= 0.0,
+            Distribution::LogNormal { mu, sigma } => {
+                mu.is_finite() && sigma.is_finite() && sigma >= 0.0
+            }
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!(
+                "{self:?} can produce negative or non-finite samples"
+            ))
+        }
+    }
+}
+
+/// Box-Muller transform.
+pub(crate) fn standard_normal<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    let u1 = 1.0 - rng.gen::<f64>();
+    let u2 = rng.gen::<f64>();
+    (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    #[test]
+    fn samples_match_their_means() {
+        let mut prng = SmallRng::seed_from_u64(42);
+        for dist in [
+            Distribution::Fixed(10.0),
+            Distribution::Uniform { min: 2.0, max: 8.0 },
+            Distribution::Exponential { mean: 100.0 },
+            Distribution::LogNormal {
+                mu: 5.0,
+                sigma: 0.5,
+            },
+        ] {
+            let n = 200_000;
+            let avg = (0..n).map(|_| dist.sample(&mut prng)).sum::<f64>() / n as f64;
+            let err = (avg - dist.mean()).abs() / dist.mean();
+            assert!(err < 0.01, "{dist:?}: {avg} vs {}", dist.mean());
+        }
+    }
+}
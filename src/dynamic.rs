@@ -0,0 +1,300 @@
+use std::collections::BTreeMap;
+
+use rand::{rngs::SmallRng, Rng, RngCore, SeedableRng};
+
+use crate::{error, fenwick::Fenwick, zonal, Backend, BackendId, LoadBalancer, PickerError, Zone};
+
+/// A zonal-affinity picker whose backend set can change in place.
+///
+/// Backends are indexed by a Fenwick tree per zone, so sampling and updating a
+/// single backend are O(log n) (plus O(zones) to pick the zone). The zonal
+/// multipliers and per-zone weights are only recomputed when some zone's
+/// total capacity changes.
+#[derive(Clone)]
+pub struct DynamicPicker {
+    zone: Zone,
+    // Demand given at construction, or `None` to assume equal demand from
+    // the client's zone and every zone that currently has backends, as
+    // [`LoadBalancer::with_seed`] does.
+    demand: Option<BTreeMap<Zone, f64>>,
+    zones: BTreeMap<Zone, ZoneIndex>,
+    // Where each backend lives: its zone and its slot in that zone's index.
+    slots: BTreeMap<BackendId, (Zone, usize)>,
+    zonal_multiplier: BTreeMap<Zone, f64>,
+    // Zones with positive weight, each with its multiplier times its total
+    // capacity, and the sum of those weights.
+    zone_weights: Vec<(Zone, f64)>,
+    total_weight: f64,
+    prng: SmallRng,
+}
+
+#[derive(Clone, Debug, Default)]
+struct ZoneIndex {
+    capacity: Fenwick,
+    ids: Vec<Option<BackendId>>,
+    // Slots vacated by removed backends, reused by later inserts.
+    free: Vec<usize>,
+}
+
+impl DynamicPicker {
+    /// Build a picker drawing its randomness from a generator seeded by `rng`.
+    pub fn from_rng(
+        zone: Zone,
+        backends: Vec<Backend>,
+        demand: &BTreeMap<Zone, f64>,
+        rng: impl RngCore,
+    ) -> Self {
+        Self::build(zone, backends, Some(demand.clone()), rng)
+    }
+    fn build(
+        zone: Zone,
+        backends: Vec<Backend>,
+        demand: Option<BTreeMap<Zone, f64>>,
+        rng: impl RngCore,
+    ) -> Self {
+        let prng = SmallRng::from_rng(rng).expect("source rng failed to produce a seed");
+        let mut picker = Self {
+            zone,
+            demand,
+            zones: BTreeMap::new(),
+            slots: BTreeMap::new(),
+            zonal_multiplier: BTreeMap::new(),
+            zone_weights: Vec::new(),
+            total_weight: 0.0,
+            prng,
+        };
+        for b in backends {
+            picker.place(b);
+        }
+        picker
+    }
+    pub fn zonal_multiplier(&self) -> &BTreeMap<Zone, f64> {
+        &self.zonal_multiplier
+    }
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+    /// Add `backend`, replacing any existing backend with the same id.
+    pub fn insert(&mut self, backend: Backend) -> Result<(), PickerError> {
+        error::validate_capacity(backend.id, backend.capacity)?;
+        self.place(backend);
+        Ok(())
+    }
+    /// Remove a backend, returning its last known state.
+    pub fn remove(&mut self, id: BackendId) -> Option<Backend> {
+        let (zone, slot) = self.slots.remove(&id)?;
+        let capacity = self.zones[&zone].capacity.get(slot);
+        self.set_slot(zone, slot, 0.0);
+        let index = self.zones.get_mut(&zone).unwrap();
+        index.ids[slot] = None;
+        index.free.push(slot);
+        if index.free.len() == index.ids.len() {
+            self.zones.remove(&zone);
+            self.recompute_multipliers();
+        }
+        Some(Backend { id, zone, capacity })
+    }
+    /// Change the capacity of an existing backend.
+    pub fn set_capacity(&mut self, id: BackendId, capacity: f64) -> Result<(), PickerError> {
+        error::validate_capacity(id, capacity)?;
+        let Some(&(zone, slot)) = self.slots.get(&id) else {
+            return Err(PickerError::UnknownBackend { backend: id });
+        };
+        self.set_slot(zone, slot, capacity);
+        Ok(())
+    }
+
+    fn place(&mut self, backend: Backend) {
+        if let Some(&(zone, slot)) = self.slots.get(&backend.id) {
+            if zone == backend.zone {
+                self.set_slot(zone, slot, backend.capacity);
+                return;
+            }
+            self.remove(backend.id);
+        }
+        let index = self.zones.entry(backend.zone).or_default();
+        let slot = match index.free.pop() {
+            Some(slot) => {
+                index.ids[slot] = Some(backend.id);
+                slot
+            }
+            None => {
+                index.ids.push(Some(backend.id));
+                index.capacity.push(0.0)
+            }
+        };
+        self.slots.insert(backend.id, (backend.zone, slot));
+        self.set_slot(backend.zone, slot, backend.capacity);
+    }
+
+    fn set_slot(&mut self, zone: Zone, slot: usize, capacity: f64) {
+        let index = self.zones.get_mut(&zone).unwrap();
+        if index.capacity.get(slot) == capacity {
+            return;
+        }
+        index.capacity.set(slot, capacity);
+        self.recompute_multipliers();
+    }
+    fn recompute_multipliers(&mut self) {
+        let per_zone_capacity: BTreeMap<Zone, f64> = self
+            .zones
+            .iter()
+            .map(|(&z, index)| (z, index.capacity.total()))
+            .collect();
+        let demand = match &self.demand {
+            Some(demand) => demand.clone(),
+            None => zonal::uniform_demand(self.zones.keys().copied().chain([self.zone])),
+        };
+        self.zonal_multiplier = zonal::zonal_multipliers(self.zone, &per_zone_capacity, &demand);
+        self.zone_weights = self
+            .zonal_multiplier
+            .iter()
+            .filter_map(|(z, &lambda)| {
+                let weight = lambda * per_zone_capacity.get(z)?;
+                (weight > 0.0).then_some((*z, weight))
+            })
+            .collect();
+        self.total_weight = self.zone_weights.iter().map(|(_, w)| w).sum();
+    }
+}
+
+impl LoadBalancer for DynamicPicker {
+    /// Unlike [`LoadBalancer::with_demand`], the assumed demand follows the
+    /// zones that have backends as they are inserted and removed.
+    fn with_seed(zone: Zone, backends: Vec<Backend>, seed: u64) -> Self {
+        Self::build(zone, backends, None, SmallRng::seed_from_u64(seed))
+    }
+    fn with_demand(
+        zone: Zone,
+        backends: Vec<Backend>,
+        demand: &BTreeMap<Zone, f64>,
+        seed: u64,
+    ) -> Self {
+        Self::from_rng(zone, backends, demand, SmallRng::seed_from_u64(seed))
+    }
+    fn sample(&mut self) -> Option<BackendId> {
+        if self.total_weight.is_nan() || self.total_weight <= 0.0 {
+            return None;
+        }
+        let mut target = self.prng.gen::<f64>() * self.total_weight;
+        let mut zone = self.zone_weights.last()?.0;
+        for &(z, w) in &self.zone_weights {
+            if target < w {
+                zone = z;
+                break;
+            }
+            target -= w;
+        }
+        let index = &self.zones[&zone];
+        let slot = index
+            .capacity
+            .find(self.prng.gen::<f64>() * index.capacity.total())?;
+        index.ids[slot]
+    }
+    fn weights(&self) -> BTreeMap<BackendId, f64> {
+        let mut acc = BTreeMap::new();
+        let mut total_weight = 0.0;
+        for (&id, &(zone, slot)) in &self.slots {
+            let Some(&lambda) = self.zonal_multiplier.get(&zone) else {
+                continue;
+            };
+            let weight = lambda * self.zones[&zone].capacity.get(slot);
+            total_weight += weight;
+            acc.insert(id, weight);
+        }
+        if total_weight > 0.0 {
+            for w in acc.values_mut() {
+                *w /= total_weight;
+            }
+        }
+        acc
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Picker;
+
+    fn backend(id: u32, zone: u8, capacity: f64) -> Backend {
+        Backend {
+            id: BackendId(id),
+            zone: Zone(zone),
+            capacity,
+        }
+    }
+
+    fn assert_same_weights(a: &BTreeMap<BackendId, f64>, b: &BTreeMap<BackendId, f64>) {
+        let ids: std::collections::BTreeSet<_> = a.keys().chain(b.keys()).collect();
+        for id in ids {
+            let (x, y) = (
+                a.get(id).copied().unwrap_or_default(),
+                b.get(id).copied().unwrap_or_default(),
+            );
+            assert!((x - y).abs() < 1e-9, "{id:?}: {x} != {y}");
+        }
+    }
+
+    #[test]
+    fn updates_match_a_rebuilt_picker() {
+        let mut backends: Vec<Backend> = (0..12)
+            .map(|i| backend(i, b'a' + (i % 3) as u8, 1.0))
+            .collect();
+        let mut dynamic = DynamicPicker::new(Zone(b'a'), backends.clone());
+        assert_same_weights(
+            &dynamic.weights(),
+            &Picker::new(Zone(b'a'), backends.clone()).weights(),
+        );
+
+        dynamic.set_capacity(BackendId(1), 4.0).unwrap();
+        backends[1].capacity = 4.0;
+        dynamic.remove(BackendId(3));
+        backends.remove(3);
+        dynamic.insert(backend(20, b'c', 2.5)).unwrap();
+        backends.push(backend(20, b'c', 2.5));
+        assert_same_weights(
+            &dynamic.weights(),
+            &Picker::new(Zone(b'a'), backends.clone()).weights(),
+        );
+
+        // A backend in a new zone brings that zone's clients into the
+        // assumed demand, just as rebuilding the picker would.
+        dynamic.insert(backend(30, b'd', 6.0)).unwrap();
+        backends.push(backend(30, b'd', 6.0));
+        assert_same_weights(
+            &dynamic.weights(),
+            &Picker::new(Zone(b'a'), backends.clone()).weights(),
+        );
+
+        // Bad updates are rejected and leave the picker untouched.
+        let before = dynamic.weights();
+        assert_eq!(
+            dynamic.set_capacity(BackendId(1), -5.0),
+            Err(PickerError::InvalidCapacity {
+                backend: BackendId(1),
+                capacity: -5.0
+            })
+        );
+        assert!(dynamic.insert(backend(21, b'a', f64::NAN)).is_err());
+        assert_eq!(
+            dynamic.set_capacity(BackendId(99), 1.0),
+            Err(PickerError::UnknownBackend {
+                backend: BackendId(99)
+            })
+        );
+        assert_same_weights(&dynamic.weights(), &before);
+
+        let iterations = 200_000;
+        let mut tally: BTreeMap<BackendId, usize> = BTreeMap::new();
+        for _ in 0..iterations {
+            *tally.entry(dynamic.sample().unwrap()).or_default() += 1;
+        }
+        for (id, w) in dynamic.weights() {
+            let observed = tally.get(&id).copied().unwrap_or_default() as f64 / iterations as f64;
+            assert!((observed - w).abs() < 0.01, "{id:?}: {observed} vs {w}");
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BinaryHeap},
+};
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::{zonal, Backend, BackendId, LoadBalancer, Zone};
+
+/// Envoy's earliest-deadline-first weighted round-robin over the
+/// zonal-affinity weights.
+///
+/// Each backend is queued with a deadline of `1 / weight` past the scheduler's
+/// clock. A pick takes the earliest deadline, breaking ties by insertion order,
+/// advances the clock to it, and requeues the backend one period later. Like
+/// Envoy, a new scheduler first makes `seed % backends` picks, with `seed`
+/// drawn at random, so that clients built at the same moment do not all send
+/// their first requests to the same backend.
+#[derive(Clone, Debug)]
+pub struct EdfPicker {
+    queue: BinaryHeap<Entry>,
+    now: f64,
+    // Tie-breaker handed to the next entry queued.
+    order: u64,
+    weights: BTreeMap<BackendId, f64>,
+}
+impl EdfPicker {
+    /// Build a scheduler over `weights` and make `picks` picks modulo their
+    /// number, as Envoy does with its per-balancer random seed.
+    pub fn with_offset(weights: impl IntoIterator<Item = (BackendId, f64)>, picks: u64) -> Self {
+        let weights: Vec<_> = weights.into_iter().filter(|&(_, w)| w > 0.0).collect();
+        let mut edf = Self {
+            queue: BinaryHeap::with_capacity(weights.len()),
+            now: 0.0,
+            order: 0,
+            weights: weights.iter().copied().collect(),
+        };
+        for &(id, weight) in &weights {
+            edf.add(id, weight);
+        }
+        if !weights.is_empty() {
+            for _ in 0..picks % weights.len() as u64 {
+                edf.sample();
+            }
+        }
+        edf
+    }
+    fn add(&mut self, id: BackendId, weight: f64) {
+        self.queue.push(Entry {
+            deadline: self.now + 1.0 / weight,
+            order: self.order,
+            id,
+            weight,
+        });
+        self.order += 1;
+    }
+}
+impl LoadBalancer for EdfPicker {
+    fn with_demand(
+        zone: Zone,
+        backends: Vec<Backend>,
+        demand: &BTreeMap<Zone, f64>,
+        seed: u64,
+    ) -> Self {
+        let picks = SmallRng::seed_from_u64(seed).gen();
+        Self::with_offset(zonal::backend_weights(zone, &backends, demand), picks)
+    }
+    fn sample(&mut self) -> Option<BackendId> {
+        let Entry {
+            deadline,
+            id,
+            weight,
+            ..
+        } = self.queue.pop()?;
+        self.now = deadline;
+        self.add(id, weight);
+        Some(id)
+    }
+    fn weights(&self) -> BTreeMap<BackendId, f64> {
+        let total: f64 = self.weights.values().sum();
+        self.weights
+            .iter()
+            .map(|(&id, w)| (id, w / total))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    deadline: f64,
+    order: u64,
+    id: BackendId,
+    weight: f64,
+}
+// Reversed, so that the max-heap pops the earliest deadline first.
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .deadline
+            .total_cmp(&self.deadline)
+            .then(other.order.cmp(&self.order))
+    }
+}
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Entry {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sim;
+
+    #[test]
+    fn schedules_by_deadline_from_an_offset() {
+        // Deadlines for weights 2, 1, 1 fall at 0.5, 1, 1, 1, 1.5, 2, 2, 2,
+        // with ties going to whichever backend was queued first.
+        let weights = [
+            (BackendId(0), 2.0),
+            (BackendId(1), 1.0),
+            (BackendId(2), 1.0),
+        ];
+        let mut edf = EdfPicker::with_offset(weights, 0);
+        let picks: Vec<u32> = (0..8).map(|_| edf.sample().unwrap().0).collect();
+        assert_eq!(picks, [0, 1, 2, 0, 0, 1, 2, 0]);
+
+        // An offset of 4 (mod 3) starts one pick into the same schedule.
+        let mut offset = EdfPicker::with_offset(weights, 4);
+        let later: Vec<u32> = (0..7).map(|_| offset.sample().unwrap().0).collect();
+        assert_eq!(later, picks[1..]);
+
+        // Clients seeded differently start at different points, but each
+        // stays exact over short windows.
+        let backends = sim::topology(&[(Zone(b'a'), 1, 1.0), (Zone(b'b'), 5, 1.0)]);
+        let demand = zonal::uniform_demand([Zone(b'a'), Zone(b'b')]);
+        let first =
+            |seed| EdfPicker::with_demand(Zone(b'b'), backends.clone(), &demand, seed).sample();
+        assert!((0..16).any(|seed| first(seed) != first(0)));
+        let spread = sim::window_spread::<EdfPicker>(&backends, &demand, 1_200, 20, 7);
+        assert!(spread.worst < 1.05, "{spread}");
+    }
+}
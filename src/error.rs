@@ -0,0 +1,189 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::{Backend, BackendId, Zone};
+
+/// Why a picker could not be built from a topology.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PickerError {
+    /// The client in `zone` was given no backends at all.
+    EmptyBackends { zone: Zone },
+    /// A backend's capacity is NaN, infinite or negative.
+    InvalidCapacity { backend: BackendId, capacity: f64 },
+    /// Two backends share the same id.
+    DuplicateBackend { backend: BackendId },
+    /// Every backend visible to the client in `zone` has zero capacity.
+    ZeroTotalCapacity { zone: Zone },
+    /// The request rate declared for clients in `zone` is NaN, infinite or negative.
+    InvalidDemand { zone: Zone, rate: f64 },
+    /// The backends' capacities are each finite but sum to infinity.
+    CapacityOverflow,
+    /// The zones' request rates are each finite but sum to infinity.
+    DemandOverflow,
+    /// A Maglev table size is not a prime at least as large as the number
+    /// of backends, so permutations would not cover every slot.
+    InvalidTableSize { size: usize, backends: usize },
+    /// A bounded-load headroom is NaN, infinite or not positive, so some
+    /// keys would find every backend full.
+    InvalidEpsilon { epsilon: f64 },
+    /// An update named a backend the picker does not know about.
+    UnknownBackend { backend: BackendId },
+}
+impl fmt::Display for PickerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PickerError::EmptyBackends { zone } => {
+                write!(f, "client in zone {zone} has no backends")
+            }
+            PickerError::InvalidCapacity { backend, capacity } => {
+                write!(f, "backend {} has invalid capacity {capacity}", backend.0)
+            }
+            PickerError::DuplicateBackend { backend } => {
+                write!(f, "backend {} appears more than once", backend.0)
+            }
+            PickerError::ZeroTotalCapacity { zone } => {
+                write!(f, "client in zone {zone} sees zero total capacity")
+            }
+            PickerError::InvalidDemand { zone, rate } => {
+                write!(f, "zone {zone} has invalid demand {rate}")
+            }
+            PickerError::CapacityOverflow => {
+                write!(f, "total backend capacity is too large to represent")
+            }
+            PickerError::DemandOverflow => {
+                write!(f, "total demand is too large to represent")
+            }
+            PickerError::InvalidTableSize { size, backends } => write!(
+                f,
+                "table size {size} must be a prime of at least {}",
+                (*backends).max(2)
+            ),
+            PickerError::InvalidEpsilon { epsilon } => {
+                write!(f, "epsilon {epsilon} must be finite and positive")
+            }
+            PickerError::UnknownBackend { backend } => {
+                write!(f, "backend {} is not known", backend.0)
+            }
+        }
+    }
+}
+impl std::error::Error for PickerError {}
+
+/// Check that `backends` form a topology a client in `zone` can route over.
+pub fn validate(zone: Zone, backends: &[Backend]) -> Result<(), PickerError> {
+    if backends.is_empty() {
+        return Err(PickerError::EmptyBackends { zone });
+    }
+    let mut seen = BTreeSet::new();
+    for b in backends {
+        validate_capacity(b.id, b.capacity)?;
+        if !seen.insert(b.id) {
+            return Err(PickerError::DuplicateBackend { backend: b.id });
+        }
+    }
+    let total: f64 = backends.iter().map(|b| b.capacity).sum();
+    if total == 0.0 {
+        return Err(PickerError::ZeroTotalCapacity { zone });
+    }
+    if !total.is_finite() {
+        return Err(PickerError::CapacityOverflow);
+    }
+    Ok(())
+}
+
+/// Check that `capacity` is usable as a weight for `backend`.
+pub fn validate_capacity(backend: BackendId, capacity: f64) -> Result<(), PickerError> {
+    if !capacity.is_finite() || capacity < 0.0 {
+        return Err(PickerError::InvalidCapacity { backend, capacity });
+    }
+    Ok(())
+}
+
+/// Check that every zone's request rate is usable as a weight.
+pub fn validate_demand(demand: &BTreeMap<Zone, f64>) -> Result<(), PickerError> {
+    for (&zone, &rate) in demand {
+        if !rate.is_finite() || rate < 0.0 {
+            return Err(PickerError::InvalidDemand { zone, rate });
+        }
+    }
+    if !demand.values().sum::<f64>().is_finite() {
+        return Err(PickerError::DemandOverflow);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{LoadBalancer, Picker};
+
+    fn backend(id: u32, zone: u8, capacity: f64) -> Backend {
+        Backend {
+            id: BackendId(id),
+            zone: Zone(zone),
+            capacity,
+        }
+    }
+
+    #[test]
+    fn rejects_degenerate_topologies() {
+        let zone = Zone(b'a');
+        let cases = [
+            (vec![], PickerError::EmptyBackends { zone }),
+            (
+                vec![backend(0, b'a', 1.0), backend(1, b'b', -1.0)],
+                PickerError::InvalidCapacity {
+                    backend: BackendId(1),
+                    capacity: -1.0,
+                },
+            ),
+            (
+                vec![backend(0, b'a', 1.0), backend(0, b'b', 1.0)],
+                PickerError::DuplicateBackend {
+                    backend: BackendId(0),
+                },
+            ),
+            (
+                vec![backend(0, b'a', 0.0), backend(1, b'b', 0.0)],
+                PickerError::ZeroTotalCapacity { zone },
+            ),
+            (
+                vec![backend(0, b'a', 1e308), backend(1, b'b', 1e308)],
+                PickerError::CapacityOverflow,
+            ),
+        ];
+        for (backends, want) in cases {
+            assert_eq!(Picker::try_new(zone, backends).err(), Some(want));
+        }
+        let err = Picker::try_new(zone, vec![backend(3, b'a', f64::NAN)])
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            PickerError::InvalidCapacity {
+                backend: BackendId(3),
+                ..
+            }
+        ));
+        assert!(Picker::try_new(zone, vec![backend(0, b'b', 2.0)]).is_ok());
+
+        let demand = [(Zone(b'a'), 1.0), (Zone(b'b'), -2.0)]
+            .into_iter()
+            .collect();
+        let err = Picker::try_with_demand(zone, vec![backend(0, b'b', 2.0)], &demand, 0).err();
+        assert_eq!(
+            err,
+            Some(PickerError::InvalidDemand {
+                zone: Zone(b'b'),
+                rate: -2.0
+            })
+        );
+        let demand = [(Zone(b'a'), 1e308), (Zone(b'b'), 1e308)]
+            .into_iter()
+            .collect();
+        let err = Picker::try_with_demand(zone, vec![backend(0, b'b', 2.0)], &demand, 0).err();
+        assert_eq!(err, Some(PickerError::DemandOverflow));
+    }
+}
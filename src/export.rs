@@ -0,0 +1,222 @@
+//! Structured export of simulation results as CSV or JSON Lines.
+//!
+//! A report becomes a flat list of records, each tagged with a `record` kind:
+//!
+//! - `summary`: totals and the overall in-zone fraction
+//! - `backend`: requests and normalized load per backend
+//! - `flow`: requests (and egress dollars) from each client zone to each backend zone
+//!
+//! Every record also carries the run parameters (seed, strategy, ...) so rows
+//! from many runs can be concatenated and still be told apart.
+
+use std::{
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use crate::{cost::EgressCost, json::Value, sim::Report};
+
+/// One flat row of output, as ordered `(column, value)` pairs.
+pub type Record = Vec<(String, Value)>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    JsonLines,
+}
+impl Format {
+    pub const ALL: &'static [Format] = &[Format::Csv, Format::JsonLines];
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::JsonLines => "jsonl",
+        }
+    }
+    pub fn write(self, w: &mut impl Write, records: &[Record]) -> io::Result<()> {
+        match self {
+            Format::Csv => write_csv(w, records),
+            Format::JsonLines => write_json_lines(w, records),
+        }
+    }
+}
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Format::ALL
+            .iter()
+            .copied()
+            .find(|format| format.name() == s)
+            .ok_or_else(|| format!("unknown format {s:?}, expected csv or jsonl"))
+    }
+}
+
+/// Flatten `report` into records, prefixing each with `params`.
+pub fn report_records(report: &Report, params: &[(String, Value)]) -> Vec<Record> {
+    let record = |kind: &str, fields: Vec<(&str, Value)>| -> Record {
+        let mut r: Record = vec![("record".to_string(), Value::from(kind))];
+        r.extend(params.iter().cloned());
+        r.extend(fields.into_iter().map(|(k, v)| (k.to_string(), v)));
+        r
+    };
+    let mut records = vec![record(
+        "summary",
+        vec![
+            ("requests", Value::from(report.total)),
+            ("unrouted", Value::from(report.unrouted)),
+            ("in_zone", Value::from(report.in_zone)),
+            ("in_zone_fraction", Value::from(report.in_zone_fraction())),
+            ("egress_dollars", dollars(&report.egress, |c| Some(c.total))),
+            (
+                "zone_blind_egress_dollars",
+                dollars(&report.zone_blind_egress, |c| Some(c.total)),
+            ),
+            ("rtt_ms", report.rtt_ms.map_or(Value::Null, Value::from)),
+            (
+                "zone_blind_rtt_ms",
+                report.zone_blind_rtt_ms.map_or(Value::Null, Value::from),
+            ),
+        ],
+    )];
+    let load = report.normalized_load();
+    for (id, &count) in &report.tally {
+        records.push(record(
+            "backend",
+            vec![
+                ("backend", Value::from(id.0)),
+                (
+                    "backend_zone",
+                    Value::from(report.backends[id].zone.to_string()),
+                ),
+                ("capacity", Value::from(report.backends[id].capacity)),
+                ("requests", Value::from(count)),
+                (
+                    "load",
+                    load.get(id).copied().map_or(Value::Null, Value::from),
+                ),
+            ],
+        ));
+    }
+    for ((client_zone, backend_zone), count) in report.flows.iter() {
+        let client_requests = report.clients[&client_zone].requests;
+        records.push(record(
+            "flow",
+            vec![
+                ("client_zone", Value::from(client_zone.to_string())),
+                ("backend_zone", Value::from(backend_zone.to_string())),
+                ("requests", Value::from(count as u64)),
+                ("fraction", Value::from(count / client_requests as f64)),
+                (
+                    "egress_dollars",
+                    dollars(&report.egress, |c| {
+                        c.per_pair.get(&(client_zone, backend_zone)).copied()
+                    }),
+                ),
+            ],
+        ));
+    }
+    records
+}
+
+fn dollars(cost: &Option<EgressCost>, f: impl Fn(&EgressCost) -> Option<f64>) -> Value {
+    cost.as_ref().and_then(f).map_or(Value::Null, Value::from)
+}
+
+/// Write one JSON object per line.
+pub fn write_json_lines(w: &mut impl Write, records: &[Record]) -> io::Result<()> {
+    for r in records {
+        writeln!(w, "{}", Value::Object(r.clone()))?;
+    }
+    Ok(())
+}
+
+/// Write a CSV whose header is the union of every record's columns, in the
+/// order they first appear. Missing and null values are left empty.
+pub fn write_csv(w: &mut impl Write, records: &[Record]) -> io::Result<()> {
+    let mut columns: Vec<&str> = Vec::new();
+    for r in records {
+        for (k, _) in r {
+            if !columns.contains(&k.as_str()) {
+                columns.push(k);
+            }
+        }
+    }
+    let header: Vec<String> = columns.iter().map(|c| csv_field(c)).collect();
+    writeln!(w, "{}", header.join(","))?;
+    for r in records {
+        let row: Vec<String> = columns
+            .iter()
+            .map(|c| match r.iter().find(|(k, _)| k == c).map(|(_, v)| v) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(s)) => csv_field(s),
+                Some(Value::Float(x)) if !x.is_finite() => String::new(),
+                Some(Value::Float(x)) => x.to_string(),
+                Some(v) => csv_field(&v.to_string()),
+            })
+            .collect();
+        writeln!(w, "{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{json, scenario::Scenario};
+
+    #[test]
+    fn exports_every_record_kind() {
+        let scenario = Scenario::parse(
+            r#"{"name": "a, b", "iterations": 100, "seed": 3,
+                "zones": {"a": {"backends": 1}, "b": {"backends": 3}}}"#,
+        )
+        .unwrap();
+        let records = report_records(&scenario.run(), &scenario.params());
+
+        let mut jsonl = Vec::new();
+        write_json_lines(&mut jsonl, &records).unwrap();
+        let lines: Vec<json::Value> = String::from_utf8(jsonl)
+            .unwrap()
+            .lines()
+            .map(|l| json::parse(l).unwrap())
+            .collect();
+        let kinds: Vec<_> = lines
+            .iter()
+            .map(|l| l.get("record").unwrap().as_str().unwrap())
+            .collect();
+        assert_eq!(kinds.iter().filter(|&&k| k == "backend").count(), 4);
+        assert!(kinds.contains(&"summary") && kinds.contains(&"flow"));
+        assert!(lines
+            .iter()
+            .all(|l| l.get("seed") == Some(&json::Value::Integer(3))));
+
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &records).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut rows = csv.lines();
+        assert_eq!(
+            rows.next().unwrap(),
+            "record,scenario,strategy,iterations,seed,requests,unrouted,in_zone,in_zone_fraction,\
+             egress_dollars,zone_blind_egress_dollars,rtt_ms,zone_blind_rtt_ms,backend,backend_zone,capacity,load,\
+             client_zone,fraction"
+        );
+        assert!(rows
+            .next()
+            .unwrap()
+            .starts_with("summary,\"a, b\",picker,100,3,200,0,"));
+        assert_eq!(rows.count(), records.len() - 1);
+    }
+}
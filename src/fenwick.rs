@@ -0,0 +1,111 @@
+/// A Fenwick (binary indexed) tree over non-negative weights.
+///
+/// Point updates, appends, prefix sums and weighted search are all O(log n).
+#[derive(Clone, Debug)]
+pub struct Fenwick {
+    // 1-indexed partial sums; `tree[0]` is unused.
+    tree: Vec<f64>,
+    values: Vec<f64>,
+}
+impl Default for Fenwick {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Fenwick {
+    pub fn new() -> Self {
+        Self {
+            tree: vec![0.0],
+            values: Vec::new(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+    pub fn get(&self, idx: usize) -> f64 {
+        self.values[idx]
+    }
+    /// Append `value` and return its index.
+    pub fn push(&mut self, value: f64) -> usize {
+        let idx = self.values.len();
+        let i = idx + 1;
+        let lowbit = i & i.wrapping_neg();
+        // `tree[i]` covers the range (i - lowbit, i].
+        let covered = self.prefix(idx) - self.prefix(i - lowbit);
+        self.tree.push(covered + value);
+        self.values.push(value);
+        idx
+    }
+    pub fn set(&mut self, idx: usize, value: f64) {
+        let delta = value - self.values[idx];
+        self.values[idx] = value;
+        let mut i = idx + 1;
+        while i < self.tree.len() {
+            self.tree[i] += delta;
+            i += i & i.wrapping_neg();
+        }
+    }
+    /// Sum of the first `n` values.
+    pub fn prefix(&self, n: usize) -> f64 {
+        let mut acc = 0.0;
+        let mut i = n;
+        while i > 0 {
+            acc += self.tree[i];
+            i -= i & i.wrapping_neg();
+        }
+        acc
+    }
+    pub fn total(&self) -> f64 {
+        self.prefix(self.len())
+    }
+    /// Find the index whose cumulative range contains `target`, which should
+    /// lie in `[0, total)`. Zero-weight entries are never returned unless every
+    /// entry is zero.
+    pub fn find(&self, target: f64) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut pos = 0;
+        let mut remaining = target;
+        let mut step = self.len().next_power_of_two();
+        while step > 0 {
+            let next = pos + step;
+            if next < self.tree.len() && self.tree[next] <= remaining {
+                pos = next;
+                remaining -= self.tree[next];
+            }
+            step >>= 1;
+        }
+        // Rounding can push us past the last positive entry; walk back to it.
+        let mut idx = pos.min(self.len() - 1);
+        while idx > 0 && self.values[idx] <= 0.0 {
+            idx -= 1;
+        }
+        Some(idx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn prefix_sums_track_updates() {
+        let mut fw = Fenwick::new();
+        for v in [1.0, 2.0, 3.0, 4.0, 5.0] {
+            fw.push(v);
+        }
+        assert_eq!(fw.total(), 15.0);
+        fw.set(2, 0.0);
+        assert_eq!(fw.prefix(3), 3.0);
+        assert_eq!(fw.total(), 12.0);
+        assert_eq!(fw.find(0.5), Some(0));
+        assert_eq!(fw.find(2.5), Some(1));
+        // Index 2 is empty, so the next range starts at 3.
+        assert_eq!(fw.find(3.0), Some(3));
+        assert_eq!(fw.find(11.9), Some(4));
+    }
+}
@@ -0,0 +1,45 @@
+/// 64-bit hashing for keys and ring points.
+///
+/// FNV-1a over the bytes, then the MurmurHash3 finalizer so that short or
+/// similar keys still spread over the whole output range. Stable across
+/// platforms and runs, unlike `std`'s `DefaultHasher`.
+pub fn hash(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in bytes {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    fmix64(h)
+}
+
+/// Hash `bytes` with a second input mixed in, e.g. a backend id or a seed.
+pub fn hash_with(bytes: &[u8], salt: u64) -> u64 {
+    fmix64(hash(bytes) ^ fmix64(salt.wrapping_add(0x9e37_79b9_7f4a_7c15)))
+}
+
+fn fmix64(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn similar_keys_spread_evenly() {
+        // Consecutive integer keys should land evenly in 16 buckets by their top bits.
+        let mut buckets = [0u32; 16];
+        for i in 0u32..160_000 {
+            buckets[(hash(&i.to_le_bytes()) >> 60) as usize] += 1;
+        }
+        for (i, &n) in buckets.iter().enumerate() {
+            assert!((9_500..10_500).contains(&n), "bucket {i}: {n}");
+        }
+        assert_ne!(hash_with(b"key", 1), hash_with(b"key", 2));
+        assert_eq!(hash(b"key"), hash(b"key"));
+    }
+}
@@ -0,0 +1,185 @@
+//! A log-linear (HDR-style) histogram of non-negative integer values.
+//!
+//! Values below `2^SUB_BUCKET_BITS` are recorded exactly; larger values land
+//! in buckets whose width is at most `2^-(SUB_BUCKET_BITS - 1)` of the value,
+//! i.e. about three significant decimal digits. Histograms with the same
+//! layout merge by adding counts, so results from parallel runs combine
+//! without losing precision.
+
+use std::fmt;
+
+const SUB_BUCKET_BITS: u32 = 10;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+const HALF: u64 = SUB_BUCKETS / 2;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    // Exact, so the mean does not suffer from bucketing error.
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+impl Histogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, value: u64) {
+        self.record_n(value, 1);
+    }
+    pub fn record_n(&mut self, value: u64, n: u64) {
+        if n == 0 {
+            return;
+        }
+        let idx = index_of(value);
+        if idx >= self.counts.len() {
+            self.counts.resize(idx + 1, 0);
+        }
+        self.counts[idx] += n;
+        self.min = if self.count == 0 {
+            value
+        } else {
+            self.min.min(value)
+        };
+        self.max = self.max.max(value);
+        self.count += n;
+        self.sum += value as u128 * n as u128;
+    }
+    /// Add every value recorded in `other` to this histogram.
+    pub fn merge(&mut self, other: &Histogram) {
+        if other.count == 0 {
+            return;
+        }
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (a, b) in self.counts.iter_mut().zip(&other.counts) {
+            *a += b;
+        }
+        self.min = if self.count == 0 {
+            other.min
+        } else {
+            self.min.min(other.min)
+        };
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.sum += other.sum;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+    pub fn min(&self) -> u64 {
+        self.min
+    }
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+    pub fn mean(&self) -> f64 {
+        self.sum as f64 / self.count as f64
+    }
+    /// The smallest recorded value that at least `q` of all values are at or
+    /// below, reported as the top of its bucket (and never above the maximum).
+    pub fn quantile(&self, q: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (idx, &c) in self.counts.iter().enumerate() {
+            seen += c;
+            if seen >= rank {
+                return highest_equivalent(idx).min(self.max);
+            }
+        }
+        self.max
+    }
+    /// Shorthand for `quantile(p / 100)`.
+    pub fn percentile(&self, p: f64) -> u64 {
+        self.quantile(p / 100.0)
+    }
+}
+
+/// The standard tail summary: `p50 / p90 / p99 / p99.9`.
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "p50 = {}  p90 = {}  p99 = {}  p99.9 = {}",
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0),
+            self.percentile(99.9)
+        )
+    }
+}
+
+fn index_of(value: u64) -> usize {
+    if value < SUB_BUCKETS {
+        return value as usize;
+    }
+    let msb = 63 - value.leading_zeros();
+    let shift = msb + 1 - SUB_BUCKET_BITS;
+    let mantissa = value >> shift;
+    (SUB_BUCKETS + (shift as u64 - 1) * HALF + (mantissa - HALF)) as usize
+}
+
+fn highest_equivalent(idx: usize) -> u64 {
+    let idx = idx as u64;
+    if idx < SUB_BUCKETS {
+        return idx;
+    }
+    let j = idx - SUB_BUCKETS;
+    let shift = j / HALF + 1;
+    let mantissa = j % HALF + HALF;
+    let top = ((mantissa as u128 + 1) << shift) - 1;
+    top.min(u64::MAX as u128) as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quantiles_are_within_bucket_precision() {
+        let mut h = Histogram::new();
+        for v in 1..=1_000_000 {
+            h.record(v);
+        }
+        assert_eq!(h.count(), 1_000_000);
+        assert_eq!(h.mean(), 500_000.5);
+        for (p, exact) in [(50.0, 500_000.0), (99.0, 990_000.0), (99.9, 999_000.0)] {
+            let got = h.percentile(p) as f64;
+            assert!(
+                got >= exact && (got - exact) / exact < 0.002,
+                "p{p} = {got}"
+            );
+        }
+        assert_eq!(h.percentile(100.0), 1_000_000);
+        assert_eq!(h.percentile(0.0), 1);
+    }
+
+    #[test]
+    fn merging_matches_recording_together() {
+        let (mut a, mut b, mut both) = (Histogram::new(), Histogram::new(), Histogram::new());
+        for v in 0..5_000u64 {
+            let v = v * v;
+            if v % 3 == 0 {
+                a.record(v);
+            } else {
+                b.record(v);
+            }
+            both.record(v);
+        }
+        a.merge(&b);
+        assert_eq!(a, both);
+        for v in [0u64, 1, 1023, 1024, 1025, 123_456_789, u64::MAX] {
+            assert!(highest_equivalent(index_of(v)) >= v);
+        }
+    }
+}
@@ -0,0 +1,485 @@
+//! Load spread and key stability of keyed strategies.
+//!
+//! Keys are synthetic: key `i` is the little-endian bytes of
+//! `derive_seed(seed, i)`, so runs are reproducible and keys are distinct.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::{
+    bounded::BoundedLoad,
+    maglev::Maglev,
+    rendezvous::Rendezvous,
+    ring::RingHash,
+    sim::{self, Report},
+    zonal_keyed::ZonalKeyed,
+    Backend, BackendId, KeyedBalancer, Zone,
+};
+
+/// The keyed strategies a simulation can be run with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyedStrategy {
+    Ring,
+    Maglev,
+    Rendezvous,
+    /// The ring with bounded loads, at [`BoundedLoad::DEFAULT_EPSILON`].
+    Bounded,
+}
+impl KeyedStrategy {
+    pub const ALL: &'static [KeyedStrategy] = &[
+        KeyedStrategy::Ring,
+        KeyedStrategy::Maglev,
+        KeyedStrategy::Rendezvous,
+        KeyedStrategy::Bounded,
+    ];
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyedStrategy::Ring => "ring",
+            KeyedStrategy::Maglev => "maglev",
+            KeyedStrategy::Rendezvous => "rendezvous",
+            KeyedStrategy::Bounded => "bounded",
+        }
+    }
+}
+/// Evaluate `$body` with the type `$K` standing for the keyed balancer that
+/// `$strategy` names. This is the one place a [`KeyedStrategy`] is mapped to a type.
+macro_rules! with_keyed_strategy {
+    ($strategy:expr, $K:ident => $body:expr) => {
+        match $strategy {
+            KeyedStrategy::Ring => {
+                type $K = RingHash;
+                $body
+            }
+            KeyedStrategy::Maglev => {
+                type $K = Maglev;
+                $body
+            }
+            KeyedStrategy::Rendezvous => {
+                type $K = Rendezvous;
+                $body
+            }
+            KeyedStrategy::Bounded => {
+                type $K = BoundedLoad;
+                $body
+            }
+        }
+    };
+}
+
+impl fmt::Display for KeyedStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+impl FromStr for KeyedStrategy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KeyedStrategy::ALL
+            .iter()
+            .copied()
+            .find(|strategy| strategy.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = KeyedStrategy::ALL.iter().map(|s| s.name()).collect();
+                format!(
+                    "unknown keyed strategy {s:?}, expected one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// The bytes of synthetic key `index`.
+pub fn key(seed: u64, index: u64) -> [u8; 8] {
+    sim::derive_seed(seed, index).to_le_bytes()
+}
+
+/// How many of a run's keys each backend received.
+#[derive(Clone, Debug)]
+pub struct KeyedReport {
+    pub backends: BTreeMap<BackendId, Backend>,
+    pub tally: BTreeMap<BackendId, u64>,
+    pub keys: u64,
+    /// Keys for which there was no backend.
+    pub unrouted: u64,
+    /// Wall-clock time to build the strategy's lookup structure.
+    pub build_time: Duration,
+    /// Wall-clock time to route every key.
+    pub lookup_time: Duration,
+}
+impl KeyedReport {
+    /// Each backend's key count relative to its share of total capacity, so a
+    /// perfectly balanced run has every entry at 1.0. Backends with no
+    /// capacity are omitted.
+    pub fn normalized_load(&self) -> BTreeMap<BackendId, f64> {
+        let counts = self.tally.iter().map(|(&id, &n)| (id, n as f64));
+        sim::normalized_load(counts, &self.backends)
+    }
+    /// The highest normalized load, the usual measure of a hash's imbalance.
+    pub fn max_load(&self) -> f64 {
+        self.normalized_load().values().copied().fold(0.0, f64::max)
+    }
+    pub fn min_load(&self) -> f64 {
+        self.normalized_load()
+            .values()
+            .copied()
+            .fold(f64::INFINITY, f64::min)
+    }
+}
+impl fmt::Display for KeyedReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (id, load) in self.normalized_load() {
+            writeln!(f, "[{}] {load:.5}", self.backends[&id].zone)?;
+        }
+        writeln!(
+            f,
+            "min load = {:.5}  max load = {:.5}",
+            self.min_load(),
+            self.max_load()
+        )?;
+        write!(
+            f,
+            "build time = {:.3}ms  lookup time = {:.1}ns",
+            1e3 * self.build_time.as_secs_f64(),
+            1e9 * self.lookup_time.as_secs_f64() / self.keys.max(1) as f64
+        )
+    }
+}
+
+/// Route `keys` synthetic keys through a `K` over `backends` and count where they land.
+pub fn spread<K: KeyedBalancer>(backends: &[Backend], keys: u64, seed: u64) -> KeyedReport {
+    let start = Instant::now();
+    let mut lb = K::from_backends(backends.to_vec());
+    let build_time = start.elapsed();
+    let mut report = KeyedReport {
+        backends: backends.iter().map(|b| (b.id, b.clone())).collect(),
+        tally: backends.iter().map(|b| (b.id, 0)).collect(),
+        keys,
+        unrouted: 0,
+        build_time,
+        lookup_time: Duration::ZERO,
+    };
+    let start = Instant::now();
+    for i in 0..keys {
+        match lb.sample_key(&key(seed, i)) {
+            Some(id) => *report.tally.entry(id).or_default() += 1,
+            None => report.unrouted += 1,
+        }
+    }
+    report.lookup_time = start.elapsed();
+    report
+}
+
+/// The fraction of `keys` synthetic keys that map to a different backend
+/// after the backend set changes from `before` to `after`.
+pub fn remap_fraction<K: KeyedBalancer>(
+    before: &[Backend],
+    after: &[Backend],
+    keys: u64,
+    seed: u64,
+) -> f64 {
+    let mut before = K::from_backends(before.to_vec());
+    let mut after = K::from_backends(after.to_vec());
+    let moved = (0..keys)
+        .filter(|&i| {
+            let key = key(seed, i);
+            before.sample_key(&key) != after.sample_key(&key)
+        })
+        .count();
+    moved as f64 / keys as f64
+}
+
+/// The keys moved by one change to the backend set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Disruption {
+    /// The fraction of keys that map to a different backend afterwards.
+    pub remapped: f64,
+    /// The fraction that had to move: the removed backend's share of
+    /// capacity before the change, or the added backend's share after it.
+    pub minimal: f64,
+}
+
+/// Alternately remove a random backend and add a new one like a random
+/// survivor, `changes` times, measuring how many keys each change moves.
+pub fn churn<K: KeyedBalancer>(
+    backends: &[Backend],
+    changes: usize,
+    keys: u64,
+    seed: u64,
+) -> Vec<Disruption> {
+    let mut prng = SmallRng::seed_from_u64(seed);
+    let mut current = backends.to_vec();
+    let mut next_id = backends.iter().map(|b| b.id.0 + 1).max().unwrap_or(0);
+    let mut steps = Vec::with_capacity(changes);
+    for step in 0..changes {
+        let before_capacity: f64 = current.iter().map(|b| b.capacity).sum();
+        let mut after = current.clone();
+        let minimal = if step % 2 == 0 && after.len() > 1 {
+            let removed = after.remove(prng.gen_range(0..after.len()));
+            removed.capacity / before_capacity
+        } else {
+            let added = Backend {
+                id: BackendId(next_id),
+                ..after[prng.gen_range(0..after.len())].clone()
+            };
+            next_id += 1;
+            let share = added.capacity / (before_capacity + added.capacity);
+            after.push(added);
+            share
+        };
+        let remapped =
+            remap_fraction::<K>(&current, &after, keys, sim::derive_seed(seed, step as u64));
+        steps.push(Disruption { remapped, minimal });
+        current = after;
+    }
+    steps
+}
+
+/// Popularity-skewed requests over `keys` distinct keys: key `i` is requested
+/// with probability proportional to `1 / (i + 1)^exponent`.
+#[derive(Clone, Debug)]
+pub struct Zipf {
+    cdf: Vec<f64>,
+}
+impl Zipf {
+    /// # Panics
+    ///
+    /// If `keys` is zero, since there would be nothing to sample, or if
+    /// `exponent` is not finite.
+    pub fn new(keys: u64, exponent: f64) -> Self {
+        assert!(keys > 0, "a Zipf distribution needs at least one key");
+        assert!(exponent.is_finite(), "Zipf exponent must be finite");
+        let mut total = 0.0;
+        let mut cdf: Vec<f64> = (0..keys)
+            .map(|i| {
+                total += ((i + 1) as f64).powf(-exponent);
+                total
+            })
+            .collect();
+        for c in &mut cdf {
+            *c /= total;
+        }
+        Self { cdf }
+    }
+    /// The index of the next requested key.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> u64 {
+        let u = rng.gen::<f64>();
+        let i = self.cdf.partition_point(|&c| c <= u);
+        i.min(self.cdf.len() - 1) as u64
+    }
+}
+
+/// One point on the bounded-load trade-off curve.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundedPoint {
+    pub epsilon: f64,
+    /// The fraction of requests served by their key's home backend on the ring.
+    pub affinity: f64,
+    /// The busiest backend's request count relative to its share of capacity.
+    pub max_load: f64,
+}
+
+/// Route `requests` Zipf-distributed requests through a [`BoundedLoad`] ring
+/// at each of `epsilons`, and measure cache affinity against the maximum
+/// load. Requests stay placed once routed, as for long-lived sessions.
+pub fn bounded_tradeoff(
+    backends: &[Backend],
+    epsilons: &[f64],
+    workload: &Zipf,
+    requests: u64,
+    seed: u64,
+) -> Vec<BoundedPoint> {
+    let by_id: BTreeMap<BackendId, Backend> = backends.iter().map(|b| (b.id, b.clone())).collect();
+    epsilons
+        .iter()
+        .map(|&epsilon| {
+            let mut lb = BoundedLoad::with_epsilon(backends, epsilon);
+            let mut prng = SmallRng::seed_from_u64(seed);
+            let mut at_home = 0;
+            for _ in 0..requests {
+                let key = key(seed, workload.sample(&mut prng));
+                let id = lb.sample_key(&key);
+                if id.is_some() && id == lb.home(&key) {
+                    at_home += 1;
+                }
+            }
+            let loads = backends.iter().map(|b| (b.id, lb.load(b.id) as f64));
+            let max_load = sim::normalized_load(loads, &by_id)
+                .into_values()
+                .fold(0.0, f64::max);
+            BoundedPoint {
+                epsilon,
+                affinity: at_home as f64 / requests as f64,
+                max_load,
+            }
+        })
+        .collect()
+}
+
+/// Route keys from a [`ZonalKeyed`] client in every zone of `demand`,
+/// sending `keys` keys per zone on average in proportion to demand, as
+/// [`sim::run_with_demand`] does for requests.
+pub fn zonal<K: KeyedBalancer>(
+    backends: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+    keys: u64,
+    seed: u64,
+) -> Report {
+    let mut report = Report::new(backends, seed, keys);
+    for (&client_zone, &count) in &zonal_key_counts(demand, keys) {
+        let mut lb = ZonalKeyed::<K>::with_demand(client_zone, backends.to_vec(), demand);
+        report.clients.entry(client_zone).or_default();
+        for i in 0..count {
+            report.record(client_zone, lb.sample_key(&key(seed, i)));
+        }
+    }
+    report
+}
+
+/// Like [`remap_fraction`], for [`ZonalKeyed`] clients in every zone of `demand`.
+pub fn zonal_remap_fraction<K: KeyedBalancer>(
+    before: &[Backend],
+    after: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+    keys: u64,
+    seed: u64,
+) -> f64 {
+    let mut moved = 0;
+    let mut total = 0;
+    for (&zone, &count) in &zonal_key_counts(demand, keys) {
+        let mut before = ZonalKeyed::<K>::with_demand(zone, before.to_vec(), demand);
+        let mut after = ZonalKeyed::<K>::with_demand(zone, after.to_vec(), demand);
+        for i in 0..count {
+            let key = key(seed, i);
+            if before.sample_key(&key) != after.sample_key(&key) {
+                moved += 1;
+            }
+        }
+        total += count;
+    }
+    moved as f64 / total as f64
+}
+
+fn zonal_key_counts(demand: &BTreeMap<Zone, f64>, keys: u64) -> BTreeMap<Zone, u64> {
+    let rates: Vec<f64> = demand.values().copied().collect();
+    let counts = sim::apportion(keys * demand.len() as u64, &rates);
+    demand.keys().copied().zip(counts).collect()
+}
+
+/// [`spread`] for a strategy chosen at runtime.
+pub fn spread_strategy(
+    strategy: KeyedStrategy,
+    backends: &[Backend],
+    keys: u64,
+    seed: u64,
+) -> KeyedReport {
+    with_keyed_strategy!(strategy, K => spread::<K>(backends, keys, seed))
+}
+
+/// [`remap_fraction`] for a strategy chosen at runtime.
+pub fn remap_fraction_strategy(
+    strategy: KeyedStrategy,
+    before: &[Backend],
+    after: &[Backend],
+    keys: u64,
+    seed: u64,
+) -> f64 {
+    with_keyed_strategy!(strategy, K => remap_fraction::<K>(before, after, keys, seed))
+}
+
+/// [`churn`] for a strategy chosen at runtime.
+pub fn churn_strategy(
+    strategy: KeyedStrategy,
+    backends: &[Backend],
+    changes: usize,
+    keys: u64,
+    seed: u64,
+) -> Vec<Disruption> {
+    with_keyed_strategy!(strategy, K => churn::<K>(backends, changes, keys, seed))
+}
+
+/// [`zonal`] for a strategy chosen at runtime.
+pub fn zonal_strategy(
+    strategy: KeyedStrategy,
+    backends: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+    keys: u64,
+    seed: u64,
+) -> Report {
+    with_keyed_strategy!(strategy, K => zonal::<K>(backends, demand, keys, seed))
+}
+
+/// [`zonal_remap_fraction`] for a strategy chosen at runtime.
+pub fn zonal_remap_fraction_strategy(
+    strategy: KeyedStrategy,
+    before: &[Backend],
+    after: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+    keys: u64,
+    seed: u64,
+) -> f64 {
+    with_keyed_strategy!(strategy, K => zonal_remap_fraction::<K>(before, after, demand, keys, seed))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Zone;
+
+    #[test]
+    fn churn_disrupts_the_ring_minimally() {
+        let backends = sim::topology(&[(Zone(b'a'), 5, 1.0), (Zone(b'b'), 5, 1.0)]);
+        for step in churn::<RingHash>(&backends, 6, 20_000, 3) {
+            assert!((step.remapped - step.minimal).abs() < 0.03, "{step:?}");
+        }
+        let maglev = churn::<Maglev>(&backends, 6, 20_000, 3);
+        let excess: f64 = maglev.iter().map(|s| s.remapped - s.minimal).sum();
+        assert!(0.0 < excess && excess < 6.0 * 0.05, "excess = {excess}");
+    }
+
+    #[test]
+    fn zonal_keys_stay_local_balanced_and_stable() {
+        // The same topology as the crate-level Picker tests: A is short of capacity.
+        let backends = sim::topology(&[
+            (Zone(b'a'), 1, 1.0),
+            (Zone(b'b'), 5, 1.0),
+            (Zone(b'c'), 9, 1.0),
+        ]);
+        let demand = [(Zone(b'a'), 1.0), (Zone(b'b'), 1.0), (Zone(b'c'), 1.0)]
+            .into_iter()
+            .collect();
+        let report = zonal::<Rendezvous>(&backends, &demand, 100_000, 3);
+        for (id, load) in report.normalized_load() {
+            assert!((0.95..1.05).contains(&load), "{id:?}: load = {load}");
+        }
+        assert!((report.in_zone_fraction() - 11.0 / 15.0).abs() < 0.01);
+
+        // Removing one of C's nine backends moves C's keys that were on it,
+        // plus the keys other zones spill into C that landed on it.
+        let fewer: Vec<Backend> = backends[..14].to_vec();
+        let moved = zonal_remap_fraction::<Rendezvous>(&backends, &fewer, &demand, 100_000, 3);
+        assert!(moved < 0.1, "moved = {moved}");
+    }
+
+    #[test]
+    fn tighter_bounds_trade_affinity_for_balance() {
+        let backends = sim::topology(&[(Zone(b'a'), 10, 1.0)]);
+        let workload = Zipf::new(1_000, 1.0);
+        let points = bounded_tradeoff(&backends, &[0.05, 0.25, 1.0, 100.0], &workload, 50_000, 5);
+        for pair in points.windows(2) {
+            assert!(pair[0].affinity <= pair[1].affinity, "{pair:?}");
+            assert!(pair[0].max_load <= pair[1].max_load, "{pair:?}");
+        }
+        for p in &points {
+            assert!(p.max_load <= 1.0 + p.epsilon + 1e-3, "{p:?}");
+        }
+        // With an effectively unbounded epsilon it is the plain ring.
+        assert_eq!(points[3].affinity, 1.0);
+        assert!(points[3].max_load > 1.5, "{:?}", points[3]);
+    }
+}
@@ -1,105 +1,171 @@
 #![allow(dead_code)]
 
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, fmt};
 
-use rand::{rngs::SmallRng, Rng, SeedableRng};
+mod alias;
+pub mod analytic;
+mod bounded;
+pub mod cost;
+pub mod des;
+pub mod distribution;
+mod dynamic;
+mod edf;
+mod error;
+pub mod export;
+mod fenwick;
+mod hash;
+pub mod histogram;
+pub mod json;
+pub mod keyed;
+mod maglev;
+pub mod matrix;
+pub mod network;
+pub mod p2c;
+mod picker;
+mod rendezvous;
+mod ring;
+pub mod scenario;
+pub mod sim;
+mod smooth;
+pub mod zonal;
+mod zonal_keyed;
+
+pub use bounded::BoundedLoad;
+pub use dynamic::DynamicPicker;
+pub use edf::EdfPicker;
+pub use error::PickerError;
+pub use maglev::Maglev;
+pub use p2c::{P2CPicker, PeakEwmaPicker};
+pub use picker::Picker;
+pub use rendezvous::Rendezvous;
+pub use ring::RingHash;
+pub use smooth::SmoothPicker;
+pub use zonal_keyed::ZonalKeyed;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-struct BackendId(u32);
+pub struct BackendId(pub u32);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-struct Zone(u8);
-
-#[derive(Clone, Debug)]
-struct Backend {
-    id: BackendId,
-    zone: Zone,
-    capacity: f64,
+pub struct Zone(pub u8);
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0 as char)
+    }
 }
 
-#[derive(Clone)]
-struct Picker {
-    // How this client should modify the backend weights in any given zone.
-    zonal_multiplier: BTreeMap<Zone, f64>,
-    backends: Vec<Backend>,
-    prng: SmallRng,
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backend {
+    pub id: BackendId,
+    pub zone: Zone,
+    pub capacity: f64,
 }
-impl Picker {
-    fn new(zone: Zone, backends: Vec<Backend>) -> Self {
-        let mut total_capacity = 0.0;
-        let per_zone_capacity = {
-            let mut acc: BTreeMap<Zone, f64> = BTreeMap::new();
-            for b in &backends {
-                total_capacity += b.capacity;
-                *acc.entry(b.zone).or_default() += b.capacity;
-            }
-            acc
-        };
-        let num_zones = per_zone_capacity.len() as f64;
-        let avg_capacity = total_capacity / num_zones;
-        let my_zone_capacity = per_zone_capacity.get(&zone).copied().unwrap_or_default();
-        let surplus_capacity: f64 = per_zone_capacity
-            .values()
-            .copied()
-            .map(|cap| {
-                if cap > avg_capacity {
-                    cap - avg_capacity
-                } else {
-                    0.0
-                }
-            })
-            .sum();
-        let zone_weights = if my_zone_capacity >= avg_capacity {
-            // If we are from an over-capacity zone, stay entirely in-zone.
-            [(zone, 1.0)].into_iter().collect()
-        } else {
-            // If we are from an under-capacity zone, we can't send _all_
-            // traffic in-zone or we'll overload our backends.  So we need to
-            // send some traffic in-zone and some cross-zone.
-            let in_zone = my_zone_capacity / avg_capacity;
-            let cross_zone = 1.0 - in_zone;
-            per_zone_capacity
-                .into_iter()
-                .map(|(z, zone_cap)| {
-                    let zone_weight = if z == zone {
-                        in_zone
-                    } else if zone_cap <= avg_capacity {
-                        // If the target zone is under-capacity, don't send any traffic.
-                        0.0
-                    } else {
-                        // Send cross-zone traffic proportional to how much of the surplus capacity
-                        // is present in that zone.
-                        cross_zone * (zone_cap - avg_capacity) / surplus_capacity
-                    };
-                    (z, zone_weight / zone_cap)
-                })
-                .collect()
-        };
-        Self {
-            zonal_multiplier: zone_weights,
-            backends,
-            prng: SmallRng::seed_from_u64(42),
-        }
+
+/// The seed used by [`LoadBalancer::new`].
+pub const DEFAULT_SEED: u64 = 42;
+
+/// A client-side load balancing strategy.
+///
+/// Each client constructs its own instance from the zone it lives in and the
+/// full list of backends, then asks it where to send each request.
+pub trait LoadBalancer {
+    fn new(zone: Zone, backends: Vec<Backend>) -> Self
+    where
+        Self: Sized,
+    {
+        Self::with_seed(zone, backends, DEFAULT_SEED)
     }
-    fn sample(&mut self) -> Option<BackendId> {
-        let mut cur: Option<BackendId> = None;
-        let mut total_weight = 0.0;
-        for b in &self.backends {
-            let Some(&lambda) = self.zonal_multiplier.get(&b.zone) else {
-                continue;
-            };
-            let weight = lambda * b.capacity;
-            total_weight += weight;
-            if self.prng.gen::<f64>() < weight / total_weight {
-                cur = Some(b.id);
-            }
-        }
-        cur
+    /// Like [`LoadBalancer::new`], but seeding any randomness the strategy uses with `seed`.
+    fn with_seed(zone: Zone, backends: Vec<Backend>, seed: u64) -> Self
+    where
+        Self: Sized,
+    {
+        let demand = zonal::uniform_demand(backends.iter().map(|b| b.zone).chain([zone]));
+        Self::with_demand(zone, backends, &demand, seed)
+    }
+    /// Build a picker that knows the relative request rate of clients in every
+    /// zone, including zones without backends. [`LoadBalancer::with_seed`]
+    /// assumes equal demand from each zone that has backends and from the
+    /// client's own zone.
+    fn with_demand(
+        zone: Zone,
+        backends: Vec<Backend>,
+        demand: &BTreeMap<Zone, f64>,
+        seed: u64,
+    ) -> Self
+    where
+        Self: Sized;
+    /// Like [`LoadBalancer::new`], but rejecting topologies that would leave
+    /// the picker with NaN weights or nothing to route to.
+    fn try_new(zone: Zone, backends: Vec<Backend>) -> Result<Self, PickerError>
+    where
+        Self: Sized,
+    {
+        Self::try_with_seed(zone, backends, DEFAULT_SEED)
+    }
+    fn try_with_seed(zone: Zone, backends: Vec<Backend>, seed: u64) -> Result<Self, PickerError>
+    where
+        Self: Sized,
+    {
+        error::validate(zone, &backends)?;
+        Ok(Self::with_seed(zone, backends, seed))
+    }
+    fn try_with_demand(
+        zone: Zone,
+        backends: Vec<Backend>,
+        demand: &BTreeMap<Zone, f64>,
+        seed: u64,
+    ) -> Result<Self, PickerError>
+    where
+        Self: Sized,
+    {
+        error::validate(zone, &backends)?;
+        error::validate_demand(demand)?;
+        Ok(Self::with_demand(zone, backends, demand, seed))
     }
+    /// Choose a backend for the next request, or `None` if there is nowhere to send it.
+    fn sample(&mut self) -> Option<BackendId>;
+    /// The probability of routing a request to each backend. Backends this
+    /// client will never pick may be omitted.
+    fn weights(&self) -> BTreeMap<BackendId, f64>;
+    /// Called when a request is sent to `backend` at `now`, in seconds.
+    /// Strategies that ignore load can leave this as a no-op.
+    fn on_dispatch(&mut self, _backend: BackendId, _now: f64) {}
+    /// Called when a request to `backend` completes at `now`, after `latency`
+    /// seconds. `utilization` is the backend's load as reported with the
+    /// response, in requests busy or queued per concurrency slot, if it
+    /// reports one.
+    fn on_complete(
+        &mut self,
+        _backend: BackendId,
+        _now: f64,
+        _latency: f64,
+        _utilization: Option<f64>,
+    ) {
+    }
+}
+
+/// A strategy that routes each request by a key, so that requests with the
+/// same key reach the same backend for as long as the backend set is stable.
+pub trait KeyedBalancer {
+    fn from_backends(backends: Vec<Backend>) -> Self
+    where
+        Self: Sized;
+    /// Choose a backend for a request with `key`, or `None` if there is nowhere to send it.
+    fn sample_key(&mut self, key: &[u8]) -> Option<BackendId>;
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::sim;
+
+    fn topology(zones: &[(u8, usize)]) -> Vec<Backend> {
+        let zones: Vec<_> = zones
+            .iter()
+            .map(|&(zone, count)| (Zone(zone), count, 1.0))
+            .collect();
+        sim::topology(&zones)
+    }
+
     #[test]
     fn zonal_affinity_is_biased_but_uniform() {
         /*
@@ -123,58 +189,91 @@ mod test {
         */
 
         let iterations = 100_000;
-        let backends: BTreeMap<BackendId, Backend> = [(b'a', 1), (b'b', 5), (b'c', 9)]
-            .into_iter()
-            .flat_map(|(zone, count)| std::iter::repeat(Zone(zone)).take(count))
-            .enumerate()
-            .map(|(idx, zone)| {
-                let id = BackendId(idx as u32);
-                (
-                    id,
-                    Backend {
-                        id,
-                        zone,
-                        capacity: 1.0,
-                    },
-                )
-            })
-            .collect();
+        let backends = topology(&[(b'a', 1), (b'b', 5), (b'c', 9)]);
 
-        let client_zones = [
-            Zone(b'a'),
-            Zone(b'b'),
-            Zone(b'c'),
-            // If there were a Zone D without any backends, clients in zones A..C won't even
-            // know it exists. That screws up their calculations and the overall
-            // distribution is skewed slightly. Uncomment this to see the skewed output.
-            // Zone(b'd'),
-        ];
-
-        let mut tally: BTreeMap<BackendId, u32> = BTreeMap::new();
-        let mut in_zone = 0;
-        let mut total = 0;
-        for client_zone in client_zones {
-            let mut picker = Picker::new(client_zone, backends.values().cloned().collect());
-            for _ in 0..iterations {
-                let b = picker.sample().unwrap();
-                *tally.entry(b).or_default() += 1;
-                if backends[&b].zone == client_zone {
-                    in_zone += 1;
-                }
-                total += 1;
-            }
-        }
+        let client_zones = [Zone(b'a'), Zone(b'b'), Zone(b'c')];
+
+        let report = sim::run::<Picker>(&backends, &client_zones, iterations, DEFAULT_SEED);
 
-        println!("{tally:#?}");
+        println!("{report}");
 
-        let avg = total as f64 / backends.len() as f64;
-        let min_load = tally.values().min().copied().unwrap() as f64 / avg;
-        let max_load = tally.values().max().copied().unwrap() as f64 / avg;
+        let load = report.normalized_load();
+        let min_load = load.values().copied().fold(f64::INFINITY, f64::min);
+        let max_load = load.values().copied().fold(0.0, f64::max);
 
         assert!(0.95 <= min_load, "min load = {min_load}");
         assert!(max_load <= 1.05, "max load = {max_load}");
 
-        let in_zone_frac = in_zone as f64 / total as f64;
+        let in_zone_frac = report.in_zone_fraction();
         assert!(in_zone_frac >= 0.733, "in_zone = {in_zone_frac}");
     }
+
+    #[test]
+    fn clients_without_local_backends_spill_evenly() {
+        // Zone D has clients but no backends. As long as every client knows D
+        // exists, everyone else leaves room for D's traffic in the surplus zones.
+        let iterations = 100_000;
+        let backends = topology(&[(b'a', 1), (b'b', 5), (b'c', 9)]);
+        let client_zones = [Zone(b'a'), Zone(b'b'), Zone(b'c'), Zone(b'd')];
+
+        let report = sim::run::<Picker>(&backends, &client_zones, iterations, DEFAULT_SEED);
+
+        let load = report.normalized_load();
+        let min_load = load.values().copied().fold(f64::INFINITY, f64::min);
+        let max_load = load.values().copied().fold(0.0, f64::max);
+
+        assert!(0.95 <= min_load, "min load = {min_load}");
+        assert!(max_load <= 1.05, "max load = {max_load}");
+
+        // A keeps 4/15 of its traffic, B and C keep all of theirs, D keeps none.
+        let in_zone_frac = report.in_zone_fraction();
+        let expected = (4.0 / 15.0 + 1.0 + 1.0 + 0.0) / 4.0;
+        assert!(
+            (in_zone_frac - expected).abs() < 0.01,
+            "in_zone = {in_zone_frac}"
+        );
+    }
+
+    #[test]
+    fn clients_in_unknown_zones_still_route() {
+        // Without explicit demand, a client in D counts its own zone as a
+        // source of traffic and spills all of it into the surplus zones.
+        let backends = topology(&[(b'a', 1), (b'b', 5), (b'c', 9)]);
+        let mut picker = Picker::try_new(Zone(b'd'), backends.clone()).unwrap();
+        assert!(picker.sample().is_some());
+        assert!(DynamicPicker::new(Zone(b'd'), backends.clone())
+            .sample()
+            .is_some());
+
+        // A client missing from explicit demand spreads over the surplus too.
+        let demand = zonal::uniform_demand([Zone(b'a'), Zone(b'b'), Zone(b'c')]);
+        let weights = Picker::with_demand(Zone(b'd'), backends.clone(), &demand, 7).weights();
+        let total: f64 = weights.values().sum();
+        assert!((total - 1.0).abs() < 1e-9, "total = {total}");
+        assert_eq!(weights[&BackendId(0)], 0.0);
+
+        // Declaring D with no demand must not strand its client either.
+        let demand = [
+            (Zone(b'a'), 1.0),
+            (Zone(b'b'), 1.0),
+            (Zone(b'c'), 1.0),
+            (Zone(b'd'), 0.0),
+        ]
+        .into_iter()
+        .collect();
+        let mut picker = Picker::try_with_demand(Zone(b'd'), backends, &demand, 7).unwrap();
+        assert!(picker.sample().is_some());
+        let total: f64 = picker.weights().values().sum();
+        assert!((total - 1.0).abs() < 1e-9, "total = {total}");
+    }
+
+    #[test]
+    fn picker_weights_sum_to_one() {
+        let backends = topology(&[(b'a', 1), (b'b', 5), (b'c', 9)]);
+        for zone in [Zone(b'a'), Zone(b'b'), Zone(b'c')] {
+            let picker = Picker::new(zone, backends.clone());
+            let total: f64 = picker.weights().values().sum();
+            assert!((total - 1.0).abs() < 1e-9, "{zone:?}: total = {total}");
+        }
+    }
 }
@@ -0,0 +1,173 @@
+use crate::{hash, Backend, BackendId, KeyedBalancer, PickerError};
+
+/// Maglev lookup-table hashing, weighted by capacity as in Envoy.
+///
+/// Every backend walks its own permutation of the table's slots, and the
+/// backends take turns claiming their next free slot until the table is full.
+/// A backend with half the capacity of the largest takes a turn every other
+/// round. Lookups are a single hash and index.
+#[derive(Clone, Debug)]
+pub struct Maglev {
+    table: Vec<BackendId>,
+}
+impl Maglev {
+    /// The smallest prime above 65536, as in the Maglev paper.
+    pub const DEFAULT_TABLE_SIZE: usize = 65537;
+
+    /// Build a table with `size` slots. `size` must be prime, so that every
+    /// permutation visits every slot, and should be much larger than the
+    /// number of backends, so that weights are represented accurately.
+    ///
+    /// # Panics
+    ///
+    /// If `size` is rejected by [`Maglev::try_with_table_size`].
+    pub fn with_table_size(backends: &[Backend], size: usize) -> Self {
+        match Self::try_with_table_size(backends, size) {
+            Ok(maglev) => maglev,
+            Err(e) => panic!("{e}"),
+        }
+    }
+    /// Like [`Maglev::with_table_size`], but rejecting a `size` that is not a
+    /// prime of at least two and at least the number of backends.
+    pub fn try_with_table_size(backends: &[Backend], size: usize) -> Result<Self, PickerError> {
+        if size < backends.len().max(2) || !is_prime(size) {
+            return Err(PickerError::InvalidTableSize {
+                size,
+                backends: backends.len(),
+            });
+        }
+        let mut entries: Vec<Entry> = backends
+            .iter()
+            .filter(|b| b.capacity > 0.0)
+            .map(|b| {
+                let name = b.id.0.to_le_bytes();
+                Entry {
+                    id: b.id,
+                    offset: (hash::hash_with(&name, 0) % size as u64) as usize,
+                    skip: (hash::hash_with(&name, 1) % (size as u64 - 1)) as usize + 1,
+                    weight: b.capacity,
+                    target: 0.0,
+                    next: 0,
+                }
+            })
+            .collect();
+        let Some(max_weight) = entries.iter().map(|e| e.weight).reduce(f64::max) else {
+            return Ok(Self { table: Vec::new() });
+        };
+
+        let mut table: Vec<Option<BackendId>> = vec![None; size];
+        let mut filled = 0;
+        let mut round = 1.0;
+        while filled < size {
+            for e in &mut entries {
+                if filled == size {
+                    break;
+                }
+                if round * e.weight < e.target {
+                    continue;
+                }
+                e.target += max_weight;
+                let mut slot = e.slot(size);
+                while table[slot].is_some() {
+                    e.next += 1;
+                    slot = e.slot(size);
+                }
+                table[slot] = Some(e.id);
+                e.next += 1;
+                filled += 1;
+            }
+            round += 1.0;
+        }
+        Ok(Self {
+            table: table.into_iter().flatten().collect(),
+        })
+    }
+    /// The number of slots in the lookup table.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
+
+fn is_prime(n: usize) -> bool {
+    n >= 2
+        && (2..)
+            .take_while(|d| d * d <= n)
+            .all(|d| !n.is_multiple_of(d))
+}
+
+struct Entry {
+    id: BackendId,
+    offset: usize,
+    skip: usize,
+    weight: f64,
+    // The next round in which this backend takes a turn, scaled by the largest weight.
+    target: f64,
+    // How far along its permutation this backend has got.
+    next: usize,
+}
+impl Entry {
+    fn slot(&self, size: usize) -> usize {
+        ((self.offset as u64 + self.next as u64 * self.skip as u64) % size as u64) as usize
+    }
+}
+
+impl KeyedBalancer for Maglev {
+    fn from_backends(backends: Vec<Backend>) -> Self {
+        Self::with_table_size(&backends, Self::DEFAULT_TABLE_SIZE)
+    }
+    fn sample_key(&mut self, key: &[u8]) -> Option<BackendId> {
+        if self.table.is_empty() {
+            return None;
+        }
+        let slot = hash::hash(key) % self.table.len() as u64;
+        Some(self.table[slot as usize])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{keyed, sim, RingHash, Zone};
+
+    #[test]
+    fn balances_by_capacity_and_moves_few_keys() {
+        let backends = sim::topology(&[(Zone(b'a'), 4, 1.0), (Zone(b'b'), 4, 2.0)]);
+        let mut maglev = Maglev::from_backends(backends.clone());
+        assert_eq!(maglev.len(), Maglev::DEFAULT_TABLE_SIZE);
+        let mut slots = std::collections::BTreeMap::<BackendId, usize>::new();
+        for &id in &maglev.table {
+            *slots.entry(id).or_default() += 1;
+        }
+        for (id, n) in slots {
+            let expected =
+                Maglev::DEFAULT_TABLE_SIZE as f64 * backends[id.0 as usize].capacity / 12.0;
+            assert!((n as f64 - expected).abs() <= 2.0, "{id:?}: {n} slots");
+        }
+        assert_eq!(maglev.sample_key(b"k"), maglev.sample_key(b"k"));
+
+        let report = keyed::spread::<Maglev>(&backends, 100_000, 7);
+        let ring = keyed::spread::<RingHash>(&backends, 100_000, 7);
+        assert!(report.max_load() < 1.03, "max load = {}", report.max_load());
+        assert!(report.max_load() < ring.max_load());
+
+        // Maglev moves a little more than the removed backend's share.
+        let moved = keyed::remap_fraction::<Maglev>(&backends, &backends[..7], 100_000, 7);
+        let share = 2.0 / 12.0;
+        assert!(share < moved && moved < share + 0.03, "moved = {moved}");
+    }
+
+    #[test]
+    fn rejects_table_sizes_that_are_not_prime() {
+        let backends = sim::topology(&[(Zone(b'a'), 3, 1.0)]);
+        for size in [0, 1, 2, 4096] {
+            assert_eq!(
+                Maglev::try_with_table_size(&backends, size).err(),
+                Some(PickerError::InvalidTableSize { size, backends: 3 })
+            );
+        }
+        assert_eq!(Maglev::try_with_table_size(&backends, 3).unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::{
+    sim::{self, Client},
+    Backend, BackendId, LoadBalancer, Zone,
+};
+
+/// Traffic from each client zone (rows) to each backend zone (columns).
+///
+/// Entries are request counts when built from samples, and fractions of all
+/// requests when built from weights.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrafficMatrix {
+    flows: BTreeMap<(Zone, Zone), f64>,
+}
+impl TrafficMatrix {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The expected traffic of `fleet`, from each client's [`LoadBalancer::weights`]
+    /// scaled by its request rate. Entries sum to 1.
+    pub fn from_weights<L: LoadBalancer>(fleet: &[Client<L>], backends: &[Backend]) -> Self {
+        let zones = backend_zones(backends);
+        let total_rate: f64 = fleet.iter().map(|c| c.rate).sum();
+        let mut matrix = Self::new();
+        for client in fleet {
+            for (id, w) in client.lb.weights() {
+                matrix.add(client.zone, zones[&id], client.rate / total_rate * w);
+            }
+        }
+        matrix
+    }
+
+    /// Sample `fleet` and count the requests, with each client sending its
+    /// share of `requests` in proportion to its rate.
+    pub fn from_samples<L: LoadBalancer>(
+        fleet: &mut [Client<L>],
+        backends: &[Backend],
+        requests: u64,
+    ) -> Self {
+        let zones = backend_zones(backends);
+        let rates: Vec<f64> = fleet.iter().map(|c| c.rate).collect();
+        let counts = sim::apportion(requests, &rates);
+        let mut matrix = Self::new();
+        for (client, n) in fleet.iter_mut().zip(counts) {
+            for _ in 0..n {
+                if let Some(id) = client.lb.sample() {
+                    matrix.add(client.zone, zones[&id], 1.0);
+                }
+            }
+        }
+        matrix
+    }
+
+    pub fn add(&mut self, from: Zone, to: Zone, amount: f64) {
+        *self.flows.entry((from, to)).or_default() += amount;
+    }
+    pub fn get(&self, from: Zone, to: Zone) -> f64 {
+        self.flows.get(&(from, to)).copied().unwrap_or_default()
+    }
+    /// Every non-empty `(client zone, backend zone)` entry, in order.
+    pub fn iter(&self) -> impl Iterator<Item = ((Zone, Zone), f64)> + '_ {
+        self.flows.iter().map(|(&k, &v)| (k, v))
+    }
+    pub fn total(&self) -> f64 {
+        self.flows.values().sum()
+    }
+    /// Total traffic sent by clients in `from`.
+    pub fn row_total(&self, from: Zone) -> f64 {
+        self.flows
+            .range((from, Zone(u8::MIN))..=(from, Zone(u8::MAX)))
+            .map(|(_, v)| v)
+            .sum()
+    }
+    /// Total traffic received by backends in `to`.
+    pub fn column_total(&self, to: Zone) -> f64 {
+        self.iter()
+            .filter(|((_, z), _)| *z == to)
+            .map(|(_, v)| v)
+            .sum()
+    }
+    /// Traffic that stayed within its zone.
+    pub fn in_zone(&self) -> f64 {
+        self.iter()
+            .filter(|((from, to), _)| from == to)
+            .map(|(_, v)| v)
+            .sum()
+    }
+    pub fn in_zone_fraction(&self) -> f64 {
+        self.in_zone() / self.total()
+    }
+    pub fn client_zones(&self) -> BTreeSet<Zone> {
+        self.flows.keys().map(|&(from, _)| from).collect()
+    }
+    pub fn backend_zones(&self) -> BTreeSet<Zone> {
+        self.flows.keys().map(|&(_, to)| to).collect()
+    }
+    /// Scale every entry so they sum to 1.
+    pub fn normalized(&self) -> Self {
+        let total = self.total();
+        Self {
+            flows: self.flows.iter().map(|(&k, &v)| (k, v / total)).collect(),
+        }
+    }
+}
+
+/// Prints one row per client zone, with entries as fractions of all traffic.
+impl fmt::Display for TrafficMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let normalized = self.normalized();
+        let columns = self.backend_zones();
+        write!(f, "from\\to")?;
+        for to in &columns {
+            write!(f, "\t{to}")?;
+        }
+        for from in self.client_zones() {
+            write!(f, "\n{from}")?;
+            for &to in &columns {
+                write!(f, "\t{:.5}", normalized.get(from, to))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn backend_zones(backends: &[Backend]) -> BTreeMap<BackendId, Zone> {
+    backends.iter().map(|b| (b.id, b.zone)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{sim, Picker};
+
+    #[test]
+    fn sampled_matrix_converges_to_weights() {
+        let backends = sim::topology(&[
+            (Zone(b'a'), 1, 1.0),
+            (Zone(b'b'), 5, 1.0),
+            (Zone(b'c'), 9, 1.0),
+        ]);
+        let demand = [
+            (Zone(b'a'), 1.0),
+            (Zone(b'b'), 1.0),
+            (Zone(b'c'), 1.0),
+            (Zone(b'd'), 1.0),
+        ]
+        .into_iter()
+        .collect();
+        let mut fleet = sim::fleet::<Picker>(&backends, &demand, 42);
+        let exact = TrafficMatrix::from_weights(&fleet, &backends);
+        let sampled = TrafficMatrix::from_samples(&mut fleet, &backends, 400_000).normalized();
+
+        assert!((exact.total() - 1.0).abs() < 1e-9);
+        // Zone A keeps 4/15 of its quarter of the traffic and D keeps none.
+        assert!((exact.get(Zone(b'a'), Zone(b'a')) - 1.0 / 15.0).abs() < 1e-9);
+        assert_eq!(exact.row_total(Zone(b'd')), 0.25);
+        assert_eq!(exact.get(Zone(b'd'), Zone(b'd')), 0.0);
+        for ((from, to), share) in exact.iter() {
+            let diff = (share - sampled.get(from, to)).abs();
+            assert!(
+                diff < 0.005,
+                "{from}->{to}: {share} vs {}",
+                sampled.get(from, to)
+            );
+        }
+        assert_eq!(exact.backend_zones().len(), 3);
+    }
+}
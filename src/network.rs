@@ -0,0 +1,116 @@
+//! Zone-to-zone network latency.
+
+use std::collections::BTreeMap;
+
+use rand::Rng;
+
+use crate::{distribution::Distribution, matrix::TrafficMatrix, Zone};
+
+/// Round-trip time between zones, in milliseconds.
+///
+/// Every request pays the round trip from its client's zone to its backend's,
+/// plus jitter drawn independently per request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RttModel {
+    /// Round-trip milliseconds within a zone, unless overridden in `pairs`.
+    pub same_zone_ms: f64,
+    /// Round-trip milliseconds between two different zones, unless overridden in `pairs`.
+    pub cross_zone_ms: f64,
+    /// Round-trip milliseconds between a pair of zones, in either direction.
+    pub pairs: BTreeMap<(Zone, Zone), f64>,
+    /// Extra milliseconds added to each round trip, if any.
+    pub jitter_ms: Option<Distribution>,
+}
+impl Default for RttModel {
+    /// 1ms across zones, free within a zone, and no jitter.
+    fn default() -> Self {
+        Self {
+            same_zone_ms: 0.0,
+            cross_zone_ms: 1.0,
+            pairs: BTreeMap::new(),
+            jitter_ms: None,
+        }
+    }
+}
+impl RttModel {
+    /// The round trip from `client` to `backend`, without jitter.
+    pub fn base(&self, client: Zone, backend: Zone) -> f64 {
+        let pair = self
+            .pairs
+            .get(&(client, backend))
+            .or_else(|| self.pairs.get(&(backend, client)));
+        match pair {
+            Some(&ms) => ms,
+            None if client == backend => self.same_zone_ms,
+            None => self.cross_zone_ms,
+        }
+    }
+    /// The expected round trip from `client` to `backend`, including jitter.
+    pub fn mean(&self, client: Zone, backend: Zone) -> f64 {
+        self.base(client, backend) + self.jitter_ms.map_or(0.0, |j| j.mean())
+    }
+    /// Draw the round trip for one request from `client` to `backend`.
+    pub fn sample<R: Rng + ?Sized>(&self, client: Zone, backend: Zone, rng: &mut R) -> f64 {
+        self.base(client, backend) + self.jitter_ms.map_or(0.0, |j| j.sample(rng))
+    }
+    /// Check that every round trip, with any jitter, is finite and non-negative.
+    pub fn validate(&self) -> Result<(), String> {
+        let pairs = self.pairs.values();
+        for ms in [self.same_zone_ms, self.cross_zone_ms].iter().chain(pairs) {
+            if !(ms.is_finite() && *ms >= 0.0) {
+                return Err(format!(
+                    "round trip of {ms}ms must be finite and non-negative"
+                ));
+            }
+        }
+        if let Some(jitter) = &self.jitter_ms {
+            jitter.validate().map_err(|e| format!("jitter: {e}"))?;
+        }
+        Ok(())
+    }
+    /// The expected round trip per request for `flows`, whose entries are request counts.
+    pub fn mean_per_request(&self, flows: &TrafficMatrix) -> f64 {
+        let total: f64 = flows
+            .iter()
+            .map(|((client, backend), requests)| requests * self.mean(client, backend))
+            .sum();
+        total / flows.total()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{cost, sim};
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    #[test]
+    fn pairs_override_defaults_in_both_directions() {
+        let model = RttModel {
+            pairs: [((Zone(b'a'), Zone(b'c')), 2.0)].into_iter().collect(),
+            jitter_ms: Some(Distribution::Exponential { mean: 0.25 }),
+            ..RttModel::default()
+        };
+        assert_eq!(model.base(Zone(b'c'), Zone(b'a')), 2.0);
+        assert_eq!(model.base(Zone(b'a'), Zone(b'b')), 1.0);
+        assert_eq!(model.mean(Zone(b'b'), Zone(b'b')), 0.25);
+        let mut prng = SmallRng::seed_from_u64(1);
+        assert!((0..1000).all(|_| model.sample(Zone(b'a'), Zone(b'c'), &mut prng) >= 2.0));
+        assert_eq!(model.validate(), Ok(()));
+        // Jitter that could pull a round trip below zero is rejected.
+        let negative = RttModel {
+            jitter_ms: Some(Distribution::Uniform {
+                min: -1.0,
+                max: 1.0,
+            }),
+            ..RttModel::default()
+        };
+        assert!(negative.validate().is_err());
+
+        // Half the zone-blind traffic crosses zones; all of the zonal traffic stays put.
+        let backends = sim::topology(&[(Zone(b'a'), 2, 1.0), (Zone(b'b'), 2, 1.0)]);
+        let demand = [(Zone(b'a'), 1.0), (Zone(b'b'), 1.0)].into_iter().collect();
+        let blind = cost::zone_blind_flows(&backends, &demand, 100.0);
+        assert!((model.mean_per_request(&blind) - 0.75).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,256 @@
+//! Power-of-two-choices over the zonal distribution.
+
+use std::collections::BTreeMap;
+
+use rand::{rngs::SmallRng, RngCore, SeedableRng};
+
+use crate::{Backend, BackendId, LoadBalancer, Picker, Zone};
+
+/// Which load estimate a [`P2CPicker`] compares its two candidates by.
+///
+/// A client keeps one signal per backend, updated from the responses it gets.
+pub trait LoadSignal: Clone + Default {
+    /// Fold in a response from the backend that arrived at `now`, `latency`
+    /// seconds after its request was sent, and the utilization the backend
+    /// reported with it, if any.
+    fn observe(&mut self, now: f64, latency: f64, utilization: Option<f64>);
+    /// The backend's load as of `now`, with `outstanding` requests from this
+    /// client still in flight to it.
+    fn load(&self, outstanding: u32, now: f64) -> f64;
+}
+
+/// Compare the requests this client has in flight to each candidate.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Outstanding;
+impl LoadSignal for Outstanding {
+    fn observe(&mut self, _now: f64, _latency: f64, _utilization: Option<f64>) {}
+    fn load(&self, outstanding: u32, _now: f64) -> f64 {
+        outstanding as f64
+    }
+}
+
+/// Compare the utilization each candidate last reported with a response, or
+/// 0.0 if it never has.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Reported(f64);
+impl LoadSignal for Reported {
+    fn observe(&mut self, _now: f64, _latency: f64, utilization: Option<f64>) {
+        if let Some(utilization) = utilization {
+            self.0 = utilization;
+        }
+    }
+    fn load(&self, _outstanding: u32, _now: f64) -> f64 {
+        self.0
+    }
+}
+
+/// Finagle's Peak-EWMA: a moving average of latency that jumps straight to
+/// any higher observation and decays towards lower ones with time constant
+/// [`PeakEwma::DECAY_TIME`]. The load is that cost scaled by the requests
+/// in flight, plus one for the request being placed.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PeakEwma {
+    // Seconds of latency, and the time it was last updated.
+    cost: f64,
+    stamp: f64,
+}
+impl PeakEwma {
+    /// Seconds for the average to forget `1 - 1/e` of a past observation.
+    pub const DECAY_TIME: f64 = 10.0;
+    /// The load of a backend that has requests in flight but has never
+    /// answered, so that it is only picked over backends with many more.
+    const PENALTY: f64 = 1e6;
+
+    fn decayed(&self, now: f64) -> f64 {
+        self.cost * (-(now - self.stamp).max(0.0) / Self::DECAY_TIME).exp()
+    }
+}
+impl LoadSignal for PeakEwma {
+    fn observe(&mut self, now: f64, latency: f64, _utilization: Option<f64>) {
+        let w = (-(now - self.stamp).max(0.0) / Self::DECAY_TIME).exp();
+        self.cost = if latency > self.cost {
+            latency
+        } else {
+            self.cost * w + latency * (1.0 - w)
+        };
+        self.stamp = now;
+    }
+    fn load(&self, outstanding: u32, now: f64) -> f64 {
+        let cost = self.decayed(now);
+        if cost == 0.0 && outstanding > 0 {
+            Self::PENALTY + outstanding as f64
+        } else {
+            cost * (outstanding + 1) as f64
+        }
+    }
+}
+
+/// Peak-EWMA over the zonal distribution.
+pub type PeakEwmaPicker = P2CPicker<PeakEwma>;
+
+/// Zone-aware power-of-two-choices picker.
+///
+/// Draws two candidates from the same zonal-affinity distribution as
+/// [`Picker`] and routes to the less loaded of the two, keeping the first on
+/// a tie. With no load information it routes exactly like [`Picker`].
+#[derive(Clone)]
+pub struct P2CPicker<S = Outstanding> {
+    picker: Picker,
+    loads: BTreeMap<BackendId, BackendLoad<S>>,
+    // The latest time this client has heard of, in seconds.
+    now: f64,
+}
+
+#[derive(Clone, Default)]
+struct BackendLoad<S> {
+    outstanding: u32,
+    signal: S,
+}
+
+impl<S: LoadSignal> P2CPicker<S> {
+    /// Build a picker drawing its randomness from a generator seeded by `rng`.
+    pub fn from_rng(
+        zone: Zone,
+        backends: Vec<Backend>,
+        demand: &BTreeMap<Zone, f64>,
+        rng: impl RngCore,
+    ) -> Self {
+        Self::from_picker(Picker::from_rng(zone, backends, demand, rng))
+    }
+    /// Build a picker that draws candidates by capacity alone, ignoring zones.
+    pub fn zone_blind(backends: Vec<Backend>, rng: impl RngCore) -> Self {
+        Self::from_picker(Picker::zone_blind(backends, rng))
+    }
+    fn from_picker(picker: Picker) -> Self {
+        Self {
+            picker,
+            loads: BTreeMap::new(),
+            now: 0.0,
+        }
+    }
+    /// The requests this client has sent to `backend` that have not completed.
+    pub fn outstanding(&self, backend: BackendId) -> u32 {
+        self.loads.get(&backend).map_or(0, |l| l.outstanding)
+    }
+    /// The load this client currently attributes to `backend`.
+    pub fn load(&self, backend: BackendId) -> f64 {
+        match self.loads.get(&backend) {
+            Some(l) => l.signal.load(l.outstanding, self.now),
+            None => S::default().load(0, self.now),
+        }
+    }
+}
+impl<S: LoadSignal> LoadBalancer for P2CPicker<S> {
+    fn with_demand(
+        zone: Zone,
+        backends: Vec<Backend>,
+        demand: &BTreeMap<Zone, f64>,
+        seed: u64,
+    ) -> Self {
+        Self::from_rng(zone, backends, demand, SmallRng::seed_from_u64(seed))
+    }
+    fn sample(&mut self) -> Option<BackendId> {
+        let first = self.picker.sample()?;
+        let second = self.picker.sample()?;
+        if self.load(second) < self.load(first) {
+            Some(second)
+        } else {
+            Some(first)
+        }
+    }
+    /// The candidate distribution, which is also the routing distribution
+    /// while every backend is equally loaded.
+    fn weights(&self) -> BTreeMap<BackendId, f64> {
+        self.picker.weights()
+    }
+    fn on_dispatch(&mut self, backend: BackendId, now: f64) {
+        self.now = self.now.max(now);
+        self.loads.entry(backend).or_default().outstanding += 1;
+    }
+    fn on_complete(
+        &mut self,
+        backend: BackendId,
+        now: f64,
+        latency: f64,
+        utilization: Option<f64>,
+    ) {
+        self.now = self.now.max(now);
+        let load = self.loads.entry(backend).or_default();
+        load.outstanding = load.outstanding.saturating_sub(1);
+        load.signal.observe(now, latency, utilization);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        des::{self, DesConfig},
+        sim,
+    };
+
+    #[test]
+    fn avoids_the_busier_candidate() {
+        let backends = sim::topology(&[(Zone(b'a'), 2, 1.0)]);
+        let mut p2c: P2CPicker = P2CPicker::new(Zone(b'a'), backends);
+        for _ in 0..3 {
+            p2c.on_dispatch(BackendId(0), 0.0);
+        }
+        // Backend 0 only wins when it is drawn twice: a quarter of the time.
+        let picks = (0..10_000)
+            .filter(|_| p2c.sample() == Some(BackendId(0)))
+            .count();
+        assert!((2_300..2_700).contains(&picks), "picks = {picks}");
+        p2c.on_complete(BackendId(0), 1.0, 0.1, None);
+        assert_eq!(p2c.outstanding(BackendId(0)), 2);
+    }
+
+    #[test]
+    fn peak_ewma_jumps_up_and_decays_down() {
+        let mut ewma = PeakEwma::default();
+        assert_eq!(ewma.load(0, 0.0), 0.0);
+        assert!(ewma.load(1, 0.0) > ewma.load(0, 0.0) + 1e5);
+        ewma.observe(1.0, 0.010, None);
+        ewma.observe(2.0, 0.100, None);
+        assert_eq!(ewma.load(0, 2.0), 0.100);
+        assert!((ewma.load(2, 2.0) - 0.300).abs() < 1e-12);
+        // One decay time later, a fast response pulls the cost most of the way down.
+        ewma.observe(2.0 + PeakEwma::DECAY_TIME, 0.010, None);
+        let expected = 0.100 / std::f64::consts::E + 0.010 * (1.0 - 1.0 / std::f64::consts::E);
+        let cost = ewma.load(0, 2.0 + PeakEwma::DECAY_TIME);
+        assert!((cost - expected).abs() < 1e-12, "{cost} vs {expected}");
+    }
+
+    #[test]
+    fn zone_blind_candidates_follow_capacity() {
+        let backends = sim::topology(&[(Zone(b'a'), 1, 1.0), (Zone(b'b'), 1, 3.0)]);
+        let blind = PeakEwmaPicker::zone_blind(backends.clone(), SmallRng::seed_from_u64(1));
+        assert_eq!(blind.weights()[&BackendId(1)], 0.75);
+        let zonal = PeakEwmaPicker::new(Zone(b'b'), backends);
+        assert_eq!(zonal.weights().get(&BackendId(0)), None);
+    }
+
+    #[test]
+    fn cuts_tail_latency_without_leaving_the_zone() {
+        let backends = sim::topology(&[(Zone(b'a'), 4, 1.0), (Zone(b'b'), 4, 1.0)]);
+        let demand = [(Zone(b'a'), 1.0), (Zone(b'b'), 1.0)].into_iter().collect();
+        let config = DesConfig {
+            utilization: 0.9,
+            duration: 60.0,
+            ..DesConfig::default()
+        };
+        let random = des::run::<Picker>(&backends, &demand, &config);
+        for report in [
+            des::run::<P2CPicker<Outstanding>>(&backends, &demand, &config),
+            des::run::<P2CPicker<Reported>>(&backends, &demand, &config),
+            des::run::<PeakEwmaPicker>(&backends, &demand, &config),
+        ] {
+            let (p99, baseline) = (
+                report.latency().percentile(99.0),
+                random.latency().percentile(99.0),
+            );
+            assert!(3 * p99 < 2 * baseline, "p99 = {p99} vs {baseline}");
+            assert_eq!(report.in_zone_fraction(), 1.0);
+        }
+    }
+}
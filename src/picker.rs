@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+
+use rand::{rngs::SmallRng, RngCore, SeedableRng};
+
+use crate::{alias::AliasTable, zonal, Backend, BackendId, LoadBalancer, Zone};
+
+/// Zonal-affinity weighted random picker.
+///
+/// Clients prefer backends in their own zone, and spill just enough traffic
+/// into zones with surplus capacity to keep per-backend load uniform.
+#[derive(Clone)]
+pub struct Picker {
+    // How this client should modify the backend weights in any given zone.
+    zonal_multiplier: BTreeMap<Zone, f64>,
+    backends: Vec<Backend>,
+    // Backends with a positive effective weight, indexed by `table`.
+    candidates: Vec<BackendId>,
+    table: Option<AliasTable>,
+    prng: SmallRng,
+}
+impl Picker {
+    /// Build a picker drawing its randomness from a generator seeded by `rng`.
+    pub fn from_rng(
+        zone: Zone,
+        backends: Vec<Backend>,
+        demand: &BTreeMap<Zone, f64>,
+        rng: impl RngCore,
+    ) -> Self {
+        let zone_weights =
+            zonal::zonal_multipliers(zone, &zonal::per_zone_capacity(&backends), demand);
+        Self::with_multipliers(zone_weights, backends, rng)
+    }
+    /// Build a picker that ignores zones and weights backends by capacity alone.
+    pub fn zone_blind(backends: Vec<Backend>, rng: impl RngCore) -> Self {
+        let zone_weights = backends.iter().map(|b| (b.zone, 1.0)).collect();
+        Self::with_multipliers(zone_weights, backends, rng)
+    }
+    fn with_multipliers(
+        zone_weights: BTreeMap<Zone, f64>,
+        backends: Vec<Backend>,
+        rng: impl RngCore,
+    ) -> Self {
+        let prng = SmallRng::from_rng(rng).expect("source rng failed to produce a seed");
+        let (candidates, weights): (Vec<BackendId>, Vec<f64>) = backends
+            .iter()
+            .filter_map(|b| {
+                let lambda = zone_weights.get(&b.zone)?;
+                let weight = lambda * b.capacity;
+                (weight > 0.0).then_some((b.id, weight))
+            })
+            .unzip();
+        Self {
+            table: AliasTable::new(&weights),
+            candidates,
+            zonal_multiplier: zone_weights,
+            backends,
+            prng,
+        }
+    }
+    /// The per-zone factor this client applies to each backend's capacity.
+    pub fn zonal_multiplier(&self) -> &BTreeMap<Zone, f64> {
+        &self.zonal_multiplier
+    }
+}
+impl LoadBalancer for Picker {
+    fn with_demand(
+        zone: Zone,
+        backends: Vec<Backend>,
+        demand: &BTreeMap<Zone, f64>,
+        seed: u64,
+    ) -> Self {
+        Self::from_rng(zone, backends, demand, SmallRng::seed_from_u64(seed))
+    }
+    fn sample(&mut self) -> Option<BackendId> {
+        let table = self.table.as_ref()?;
+        Some(self.candidates[table.sample(&mut self.prng)])
+    }
+    fn weights(&self) -> BTreeMap<BackendId, f64> {
+        let mut acc = BTreeMap::new();
+        let mut total_weight = 0.0;
+        for b in &self.backends {
+            let Some(&lambda) = self.zonal_multiplier.get(&b.zone) else {
+                continue;
+            };
+            let weight = lambda * b.capacity;
+            total_weight += weight;
+            *acc.entry(b.id).or_default() += weight;
+        }
+        if total_weight > 0.0 {
+            for w in acc.values_mut() {
+                *w /= total_weight;
+            }
+        }
+        acc
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{hash, Backend, BackendId, KeyedBalancer};
+
+/// Weighted rendezvous (highest-random-weight) hashing.
+///
+/// Every backend scores each key as `-capacity / ln(u)`, where `u` is a hash
+/// of the key and the backend's id mapped into (0, 1), and the key goes to the
+/// highest score. That wins in proportion to capacity, needs no state beyond
+/// the backend list, and removing a backend moves only the keys it won.
+/// Lookups are O(n) in the number of backends.
+#[derive(Clone, Debug)]
+pub struct Rendezvous {
+    backends: Vec<Backend>,
+}
+impl Rendezvous {
+    /// Up to `k` distinct backends for `key`, best first, e.g. to place replicas.
+    pub fn top_k(&self, key: &[u8], k: usize) -> Vec<BackendId> {
+        let mut scored: Vec<(f64, BackendId)> = self
+            .backends
+            .iter()
+            .map(|b| (score(key, b), b.id))
+            .collect();
+        scored.sort_unstable_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+        scored.into_iter().take(k).map(|(_, id)| id).collect()
+    }
+}
+
+fn score(key: &[u8], backend: &Backend) -> f64 {
+    // The top 53 bits as a float strictly between 0 and 1.
+    let h = hash::hash_with(key, backend.id.0 as u64);
+    let u = ((h >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+    -backend.capacity / u.ln()
+}
+
+impl KeyedBalancer for Rendezvous {
+    fn from_backends(backends: Vec<Backend>) -> Self {
+        let backends = backends.into_iter().filter(|b| b.capacity > 0.0).collect();
+        Self { backends }
+    }
+    fn sample_key(&mut self, key: &[u8]) -> Option<BackendId> {
+        self.backends
+            .iter()
+            .map(|b| (score(key, b), b.id))
+            .max_by(|a, b| a.0.total_cmp(&b.0).then(b.1.cmp(&a.1)))
+            .map(|(_, id)| id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{keyed, sim, Zone};
+
+    #[test]
+    fn wins_by_capacity_and_moves_only_lost_keys() {
+        let backends = sim::topology(&[(Zone(b'a'), 4, 1.0), (Zone(b'b'), 4, 2.0)]);
+        let report = keyed::spread::<Rendezvous>(&backends, 100_000, 7);
+        for (id, load) in report.normalized_load() {
+            assert!((0.95..1.05).contains(&load), "{id:?}: load = {load}");
+        }
+        let moved = keyed::remap_fraction::<Rendezvous>(&backends, &backends[..7], 100_000, 7);
+        assert_eq!(moved, report.tally[&BackendId(7)] as f64 / 100_000.0);
+
+        let mut lb = Rendezvous::from_backends(backends);
+        let replicas = lb.top_k(b"key", 3);
+        assert_eq!(replicas.len(), 3);
+        assert_eq!(Some(replicas[0]), lb.sample_key(b"key"));
+        assert!(replicas[1] != replicas[0] && replicas[2] != replicas[1]);
+    }
+}
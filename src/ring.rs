@@ -0,0 +1,100 @@
+use crate::{hash, Backend, BackendId, KeyedBalancer};
+
+/// Ketama-style consistent hash ring.
+///
+/// Each backend is hashed onto the ring at a number of virtual nodes
+/// proportional to its capacity, and a key goes to the first virtual node at
+/// or after the key's own hash, wrapping around. Adding or removing a backend
+/// only moves the keys that land on its virtual nodes.
+#[derive(Clone, Debug)]
+pub struct RingHash {
+    // Virtual nodes sorted by their position on the ring.
+    ring: Vec<(u64, BackendId)>,
+}
+impl RingHash {
+    /// Virtual nodes for a backend of average capacity, as libketama gives
+    /// each server.
+    pub const DEFAULT_VNODES: usize = 160;
+
+    /// Build a ring with `vnodes` virtual nodes for a backend of the fleet's
+    /// mean capacity, and proportionally more or fewer for the others. As in
+    /// libketama, capacities are relative weights, so adding or removing a
+    /// backend shifts every other backend's share of the ring slightly.
+    pub fn with_vnodes(backends: &[Backend], vnodes: usize) -> Self {
+        let live: Vec<&Backend> = backends.iter().filter(|b| b.capacity > 0.0).collect();
+        let total_capacity: f64 = live.iter().map(|b| b.capacity).sum();
+        let per_capacity = vnodes as f64 * live.len() as f64 / total_capacity;
+        let mut ring = Vec::new();
+        for b in live {
+            let points = (per_capacity * b.capacity).round().max(1.0) as u64;
+            for i in 0..points {
+                ring.push((hash::hash_with(&b.id.0.to_le_bytes(), i), b.id));
+            }
+        }
+        // Ties between backends are vanishingly rare, but break them by id so
+        // the ring does not depend on the order backends were listed in.
+        ring.sort_unstable();
+        Self { ring }
+    }
+    /// The number of virtual nodes on the ring.
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+    /// The virtual nodes from `key`'s position onwards, once round the ring.
+    pub(crate) fn walk(&self, key: &[u8]) -> impl Iterator<Item = BackendId> + '_ {
+        let h = hash::hash(key);
+        let start = self.ring.partition_point(|&(point, _)| point < h);
+        let (before, after) = self.ring.split_at(start);
+        after.iter().chain(before).map(|&(_, id)| id)
+    }
+}
+impl KeyedBalancer for RingHash {
+    fn from_backends(backends: Vec<Backend>) -> Self {
+        Self::with_vnodes(&backends, Self::DEFAULT_VNODES)
+    }
+    fn sample_key(&mut self, key: &[u8]) -> Option<BackendId> {
+        self.walk(key).next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{keyed, sim, Zone};
+
+    #[test]
+    fn weights_by_capacity_and_moves_few_keys() {
+        let backends = sim::topology(&[(Zone(b'a'), 4, 1.0), (Zone(b'b'), 4, 2.0)]);
+        let ring = RingHash::from_backends(backends.clone());
+        assert_eq!(ring.len(), 8 * 160);
+
+        // A hundred-odd virtual nodes per backend leaves arcs of uneven total length.
+        let report = keyed::spread::<RingHash>(&backends, 100_000, 7);
+        for (id, load) in report.normalized_load() {
+            assert!((0.7..1.3).contains(&load), "{id:?}: load = {load}");
+        }
+
+        // Removing a backend moves the keys it held, and the few that follow
+        // the survivors' virtual nodes as they are rescaled.
+        let fewer = &backends[..7];
+        let moved = keyed::remap_fraction::<RingHash>(&backends, fewer, 100_000, 7);
+        let held = report.tally[&BackendId(7)] as f64 / 100_000.0;
+        assert!(held <= moved && moved < held + 0.05, "moved = {moved}");
+    }
+
+    #[test]
+    fn capacity_is_a_relative_weight() {
+        let scaled = |factor: f64| {
+            let mut backends = sim::topology(&[(Zone(b'a'), 4, 1.0), (Zone(b'b'), 4, 2.0)]);
+            for b in &mut backends {
+                b.capacity *= factor;
+            }
+            keyed::spread::<RingHash>(&backends, 20_000, 7).tally
+        };
+        assert_eq!(scaled(0.01), scaled(1.0));
+        assert_eq!(scaled(1000.0), scaled(1.0));
+    }
+}
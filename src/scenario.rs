@@ -0,0 +1,737 @@
+//! Declarative scenario files.
+//!
+//! A scenario is a JSON document describing the zones, backends, client
+//! populations, strategy and run length of a simulation:
+//!
+//! ```json
+//! {
+//!   "name": "one small zone",
+//!   "strategy": "picker",
+//!   "iterations": 100000,
+//!   "seed": 42,
+//!   "zones": {
+//!     "a": { "backends": 1 },
+//!     "b": { "backends": 5, "capacity": 2.0, "clients": 3.0 },
+//!     "d": { "backends": 0 }
+//!   },
+//!   "backends": [{ "id": 100, "zone": "a", "capacity": 0.5 }],
+//!   "egress": {
+//!     "cross_zone_per_gb": 0.01,
+//!     "pairs": [{ "from": "a", "to": "d", "per_gb": 0.02 }],
+//!     "request_bytes": { "fixed": 2048 },
+//!     "response_bytes": { "lognormal": { "mu": 9.0, "sigma": 1.0 } }
+//!   },
+//!   "network": {
+//!     "cross_zone_ms": 1.0,
+//!     "pairs": [{ "from": "a", "to": "b", "ms": 2.0 }],
+//!     "jitter_ms": { "exponential": 0.2 }
+//!   }
+//! }
+//! ```
+//!
+//! Every field is optional except `zones`. Each zone gets `backends` backends
+//! of the given `capacity` (default 1.0), numbered consecutively from 0 in zone
+//! order, and `clients` is the zone's relative request rate (default 1.0; 0
+//! means the zone has no clients). `backends` lists extra backends by hand.
+//! `egress`, if present, prices traffic with a [`CostModel`], and `network`
+//! adds zone-to-zone round trips with an [`RttModel`]; any of their fields
+//! may be omitted to use the defaults.
+
+use std::{collections::BTreeMap, fmt, path::Path};
+
+use crate::{
+    analytic::{self, Solution},
+    cost::CostModel,
+    des::{self, DesConfig, DesReport},
+    distribution::Distribution,
+    json::{self, Value},
+    network::RttModel,
+    sim::{self, Report, Strategy},
+    Backend, BackendId, PickerError, Zone, DEFAULT_SEED,
+};
+
+pub const DEFAULT_ITERATIONS: u64 = 100_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scenario {
+    pub name: Option<String>,
+    pub strategy: Strategy,
+    /// Requests per client zone, on average.
+    pub iterations: u64,
+    pub seed: u64,
+    pub zones: BTreeMap<Zone, ZoneSpec>,
+    /// Backends listed individually, in addition to those generated per zone.
+    pub backends: Vec<Backend>,
+    /// How to price cross-zone traffic, if at all.
+    pub egress: Option<CostModel>,
+    /// Round trips between zones, if modelled.
+    pub network: Option<RttModel>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ZoneSpec {
+    pub backends: usize,
+    pub capacity: f64,
+    /// The relative request rate of clients in this zone.
+    pub clients: f64,
+}
+impl Default for ZoneSpec {
+    fn default() -> Self {
+        Self {
+            backends: 0,
+            capacity: 1.0,
+            clients: 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    Parse(json::ParseError),
+    /// A field is missing or has the wrong type or value.
+    Field {
+        field: String,
+        message: String,
+    },
+    Topology(PickerError),
+}
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(e) => write!(f, "{e}"),
+            ScenarioError::Parse(e) => write!(f, "invalid JSON at {e}"),
+            ScenarioError::Field { field, message } => write!(f, "{field}: {message}"),
+            ScenarioError::Topology(e) => write!(f, "{e}"),
+        }
+    }
+}
+impl std::error::Error for ScenarioError {}
+impl From<std::io::Error> for ScenarioError {
+    fn from(e: std::io::Error) -> Self {
+        ScenarioError::Io(e)
+    }
+}
+impl From<json::ParseError> for ScenarioError {
+    fn from(e: json::ParseError) -> Self {
+        ScenarioError::Parse(e)
+    }
+}
+impl From<PickerError> for ScenarioError {
+    fn from(e: PickerError) -> Self {
+        ScenarioError::Topology(e)
+    }
+}
+
+fn field_error(field: impl Into<String>, message: impl Into<String>) -> ScenarioError {
+    ScenarioError::Field {
+        field: field.into(),
+        message: message.into(),
+    }
+}
+
+impl Scenario {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ScenarioError> {
+        Self::from_json(&json::parse(text)?)
+    }
+
+    pub fn from_json(doc: &Value) -> Result<Self, ScenarioError> {
+        if doc.as_object().is_none() {
+            return Err(field_error("scenario", "expected an object"));
+        }
+        let name = match doc.get("name") {
+            None | Some(Value::Null) => None,
+            Some(v) => Some(
+                v.as_str()
+                    .ok_or_else(|| field_error("name", "expected a string"))?
+                    .to_string(),
+            ),
+        };
+        let strategy = match doc.get("strategy") {
+            None => Strategy::Picker,
+            Some(v) => v
+                .as_str()
+                .ok_or_else(|| field_error("strategy", "expected a string"))?
+                .parse()
+                .map_err(|e| field_error("strategy", e))?,
+        };
+        let iterations = optional_u64(doc, "iterations")?.unwrap_or(DEFAULT_ITERATIONS);
+        let seed = optional_u64(doc, "seed")?.unwrap_or(DEFAULT_SEED);
+
+        let Some(zone_members) = doc.get("zones").and_then(Value::as_object) else {
+            return Err(field_error(
+                "zones",
+                "expected an object keyed by zone name",
+            ));
+        };
+        let mut zones = BTreeMap::new();
+        for (key, spec) in zone_members {
+            let field = format!("zones.{key}");
+            let zone = parse_zone(key).ok_or_else(|| {
+                field_error(&field, "zone names must be a single ASCII character")
+            })?;
+            if spec.as_object().is_none() {
+                return Err(field_error(field, "expected an object"));
+            }
+            let defaults = ZoneSpec::default();
+            let backends = optional_u64(spec, "backends")
+                .map_err(|_| {
+                    field_error(
+                        format!("{field}.backends"),
+                        "expected a non-negative integer",
+                    )
+                })?
+                .unwrap_or(0) as usize;
+            let zone_spec = ZoneSpec {
+                backends,
+                capacity: optional_f64(spec, &field, "capacity")?.unwrap_or(defaults.capacity),
+                clients: optional_f64(spec, &field, "clients")?.unwrap_or(defaults.clients),
+            };
+            if zones.insert(zone, zone_spec).is_some() {
+                return Err(field_error(field, "zone is listed more than once"));
+            }
+        }
+
+        let mut backends = Vec::new();
+        if let Some(list) = doc.get("backends") {
+            let list = list
+                .as_array()
+                .ok_or_else(|| field_error("backends", "expected an array"))?;
+            for (i, b) in list.iter().enumerate() {
+                let field = format!("backends[{i}]");
+                let id = b
+                    .get("id")
+                    .and_then(Value::as_u64)
+                    .and_then(|id| u32::try_from(id).ok())
+                    .ok_or_else(|| {
+                        field_error(format!("{field}.id"), "expected a 32-bit unsigned integer")
+                    })?;
+                let zone = b
+                    .get("zone")
+                    .and_then(Value::as_str)
+                    .and_then(parse_zone)
+                    .ok_or_else(|| {
+                        field_error(
+                            format!("{field}.zone"),
+                            "expected a single-character zone name",
+                        )
+                    })?;
+                let capacity = optional_f64(b, &field, "capacity")?.unwrap_or(1.0);
+                backends.push(Backend {
+                    id: BackendId(id),
+                    zone,
+                    capacity,
+                });
+            }
+        }
+
+        let egress = match doc.get("egress") {
+            None | Some(Value::Null) => None,
+            Some(v) => Some(parse_cost_model(v)?),
+        };
+        let network = match doc.get("network") {
+            None | Some(Value::Null) => None,
+            Some(v) => Some(parse_rtt_model(v)?),
+        };
+
+        let scenario = Scenario {
+            name,
+            strategy,
+            iterations,
+            seed,
+            zones,
+            backends,
+            egress,
+            network,
+        };
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Serialize back into the scenario file format, e.g. to save a scenario
+    /// that was described with command line flags.
+    pub fn to_json(&self) -> Value {
+        let mut doc = Vec::new();
+        if let Some(name) = &self.name {
+            doc.push(("name".to_string(), Value::from(name.as_str())));
+        }
+        doc.push(("strategy".to_string(), Value::from(self.strategy.name())));
+        doc.push(("iterations".to_string(), Value::from(self.iterations)));
+        doc.push(("seed".to_string(), Value::from(self.seed)));
+        let zones = self
+            .zones
+            .iter()
+            .map(|(zone, spec)| {
+                let spec = Value::Object(vec![
+                    ("backends".to_string(), Value::from(spec.backends as u64)),
+                    ("capacity".to_string(), Value::from(spec.capacity)),
+                    ("clients".to_string(), Value::from(spec.clients)),
+                ]);
+                (zone.to_string(), spec)
+            })
+            .collect();
+        doc.push(("zones".to_string(), Value::Object(zones)));
+        if !self.backends.is_empty() {
+            let backends = self
+                .backends
+                .iter()
+                .map(|b| {
+                    Value::Object(vec![
+                        ("id".to_string(), Value::from(b.id.0)),
+                        ("zone".to_string(), Value::from(b.zone.to_string())),
+                        ("capacity".to_string(), Value::from(b.capacity)),
+                    ])
+                })
+                .collect();
+            doc.push(("backends".to_string(), Value::Array(backends)));
+        }
+        if let Some(model) = &self.egress {
+            doc.push(("egress".to_string(), cost_model_to_json(model)));
+        }
+        if let Some(model) = &self.network {
+            doc.push(("network".to_string(), rtt_model_to_json(model)));
+        }
+        Value::Object(doc)
+    }
+
+    /// The run parameters to attach to exported results.
+    pub fn params(&self) -> Vec<(String, Value)> {
+        let mut params = Vec::new();
+        if let Some(name) = &self.name {
+            params.push(("scenario".to_string(), Value::from(name.as_str())));
+        }
+        params.push(("strategy".to_string(), Value::from(self.strategy.name())));
+        params.push(("iterations".to_string(), Value::from(self.iterations)));
+        params.push(("seed".to_string(), Value::from(self.seed)));
+        params
+    }
+
+    /// Every backend in the scenario: those generated per zone, then those listed by hand.
+    pub fn backends(&self) -> Vec<Backend> {
+        let generated: Vec<_> = self
+            .zones
+            .iter()
+            .map(|(&zone, spec)| (zone, spec.backends, spec.capacity))
+            .collect();
+        let mut backends = sim::topology(&generated);
+        backends.extend(self.backends.iter().cloned());
+        backends
+    }
+
+    /// The relative request rate of every zone that has clients.
+    pub fn demand(&self) -> BTreeMap<Zone, f64> {
+        self.zones
+            .iter()
+            .filter(|(_, spec)| spec.clients > 0.0)
+            .map(|(&zone, spec)| (zone, spec.clients))
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        if self.iterations == 0 {
+            return Err(field_error("iterations", "expected a positive integer"));
+        }
+        for (zone, spec) in &self.zones {
+            non_negative(spec.clients, &format!("zones.{zone}.clients"))?;
+        }
+        let demand = self.demand();
+        if demand.is_empty() {
+            return Err(field_error("zones", "no zone has clients"));
+        }
+        sim::validate(&self.backends(), &demand)?;
+        if let Some(network) = &self.network {
+            network.validate().map_err(|e| field_error("network", e))?;
+        }
+        Ok(())
+    }
+
+    /// The exact expected outcome of [`Scenario::run`], without sampling.
+    pub fn solve(&self) -> Solution {
+        analytic::solve_strategy(self.strategy, &self.backends(), &self.demand())
+    }
+
+    /// Run the scenario through the discrete-event simulator. The scenario's
+    /// seed replaces `config.seed`.
+    pub fn simulate(&self, config: &DesConfig) -> DesReport {
+        self.simulate_parallel(config, 1)
+    }
+
+    /// Like [`Scenario::simulate`], merging `runs` independent runs made in parallel.
+    pub fn simulate_parallel(&self, config: &DesConfig, runs: u64) -> DesReport {
+        let config = DesConfig {
+            seed: self.seed,
+            network: self.network.clone().or_else(|| config.network.clone()),
+            ..config.clone()
+        };
+        if runs <= 1 {
+            return des::run_strategy(self.strategy, &self.backends(), &self.demand(), &config);
+        }
+        des::run_parallel(
+            self.strategy,
+            &self.backends(),
+            &self.demand(),
+            &config,
+            runs,
+        )
+    }
+
+    pub fn run(&self) -> Report {
+        let report = sim::run_strategy(
+            self.strategy,
+            &self.backends(),
+            &self.demand(),
+            self.iterations,
+            self.seed,
+        );
+        let report = match &self.egress {
+            Some(model) => report.with_egress(model),
+            None => report,
+        };
+        match &self.network {
+            Some(model) => report.with_network(model),
+            None => report,
+        }
+    }
+}
+
+fn parse_cost_model(v: &Value) -> Result<CostModel, ScenarioError> {
+    if v.as_object().is_none() {
+        return Err(field_error("egress", "expected an object"));
+    }
+    let defaults = CostModel::default();
+    Ok(CostModel {
+        cross_zone_per_gb: optional_non_negative(v, "egress", "cross_zone_per_gb")?
+            .unwrap_or(defaults.cross_zone_per_gb),
+        same_zone_per_gb: optional_non_negative(v, "egress", "same_zone_per_gb")?
+            .unwrap_or(defaults.same_zone_per_gb),
+        pairs: parse_pairs(v, "egress", "per_gb")?,
+        request_bytes: match v.get("request_bytes") {
+            None => defaults.request_bytes,
+            Some(d) => parse_distribution(d, "egress.request_bytes")?,
+        },
+        response_bytes: match v.get("response_bytes") {
+            None => defaults.response_bytes,
+            Some(d) => parse_distribution(d, "egress.response_bytes")?,
+        },
+    })
+}
+
+fn parse_rtt_model(v: &Value) -> Result<RttModel, ScenarioError> {
+    if v.as_object().is_none() {
+        return Err(field_error("network", "expected an object"));
+    }
+    let defaults = RttModel::default();
+    Ok(RttModel {
+        same_zone_ms: optional_non_negative(v, "network", "same_zone_ms")?
+            .unwrap_or(defaults.same_zone_ms),
+        cross_zone_ms: optional_non_negative(v, "network", "cross_zone_ms")?
+            .unwrap_or(defaults.cross_zone_ms),
+        pairs: parse_pairs(v, "network", "ms")?,
+        jitter_ms: match v.get("jitter_ms") {
+            None | Some(Value::Null) => None,
+            Some(d) => Some(parse_distribution(d, "network.jitter_ms")?),
+        },
+    })
+}
+
+/// `parent.pairs`, a list of `{"from", "to", key}` objects with non-negative values.
+fn parse_pairs(
+    v: &Value,
+    parent: &str,
+    key: &str,
+) -> Result<BTreeMap<(Zone, Zone), f64>, ScenarioError> {
+    let mut pairs = BTreeMap::new();
+    let Some(list) = v.get("pairs") else {
+        return Ok(pairs);
+    };
+    let list = list
+        .as_array()
+        .ok_or_else(|| field_error(format!("{parent}.pairs"), "expected an array"))?;
+    for (i, pair) in list.iter().enumerate() {
+        let field = format!("{parent}.pairs[{i}]");
+        let zone = |name: &str| {
+            pair.get(name)
+                .and_then(Value::as_str)
+                .and_then(parse_zone)
+                .ok_or_else(|| {
+                    field_error(
+                        format!("{field}.{name}"),
+                        "expected a single-character zone name",
+                    )
+                })
+        };
+        let (from, to) = (zone("from")?, zone("to")?);
+        let value = optional_non_negative(pair, &field, key)?
+            .ok_or_else(|| field_error(format!("{field}.{key}"), "expected a number"))?;
+        pairs.insert((from, to), value);
+    }
+    Ok(pairs)
+}
+
+/// One of `{"fixed": bytes}`, `{"uniform": {"min", "max"}}`,
+/// `{"exponential": mean}` or `{"lognormal": {"mu", "sigma"}}`.
+fn parse_distribution(v: &Value, field: &str) -> Result<Distribution, ScenarioError> {
+    let expected = "expected one of {\"fixed\": n}, {\"uniform\": {\"min\", \"max\"}}, \
+                    {\"exponential\": mean} or {\"lognormal\": {\"mu\", \"sigma\"}}";
+    let Some([(kind, params)]) = v.as_object() else {
+        return Err(field_error(field, expected));
+    };
+    let field = format!("{field}.{kind}");
+    let number = |v: Option<&Value>, name: &str| {
+        v.and_then(Value::as_f64)
+            .filter(|x| x.is_finite())
+            .ok_or_else(|| field_error(format!("{field}{name}"), "expected a number"))
+    };
+    let size =
+        |v: Option<&Value>, name: &str| non_negative(number(v, name)?, &format!("{field}{name}"));
+    let dist = match kind.as_str() {
+        "fixed" => Distribution::Fixed(size(Some(params), "")?),
+        "exponential" => Distribution::Exponential {
+            mean: size(Some(params), "")?,
+        },
+        "uniform" => {
+            let (min, max) = (
+                size(params.get("min"), ".min")?,
+                size(params.get("max"), ".max")?,
+            );
+            if min > max {
+                return Err(field_error(format!("{field}.max"), "must be at least min"));
+            }
+            Distribution::Uniform { min, max }
+        }
+        "lognormal" => Distribution::LogNormal {
+            mu: number(params.get("mu"), ".mu")?,
+            sigma: size(params.get("sigma"), ".sigma")?,
+        },
+        _ => return Err(field_error(field, expected)),
+    };
+    Ok(dist)
+}
+
+fn cost_model_to_json(model: &CostModel) -> Value {
+    Value::Object(vec![
+        (
+            "cross_zone_per_gb".to_string(),
+            Value::from(model.cross_zone_per_gb),
+        ),
+        (
+            "same_zone_per_gb".to_string(),
+            Value::from(model.same_zone_per_gb),
+        ),
+        ("pairs".to_string(), pairs_to_json(&model.pairs, "per_gb")),
+        (
+            "request_bytes".to_string(),
+            distribution_to_json(&model.request_bytes),
+        ),
+        (
+            "response_bytes".to_string(),
+            distribution_to_json(&model.response_bytes),
+        ),
+    ])
+}
+
+fn rtt_model_to_json(model: &RttModel) -> Value {
+    let mut doc = vec![
+        ("same_zone_ms".to_string(), Value::from(model.same_zone_ms)),
+        (
+            "cross_zone_ms".to_string(),
+            Value::from(model.cross_zone_ms),
+        ),
+        ("pairs".to_string(), pairs_to_json(&model.pairs, "ms")),
+    ];
+    if let Some(jitter) = &model.jitter_ms {
+        doc.push(("jitter_ms".to_string(), distribution_to_json(jitter)));
+    }
+    Value::Object(doc)
+}
+
+fn pairs_to_json(pairs: &BTreeMap<(Zone, Zone), f64>, key: &str) -> Value {
+    let pairs = pairs
+        .iter()
+        .map(|(&(from, to), &value)| {
+            Value::Object(vec![
+                ("from".to_string(), Value::from(from.to_string())),
+                ("to".to_string(), Value::from(to.to_string())),
+                (key.to_string(), Value::from(value)),
+            ])
+        })
+        .collect();
+    Value::Array(pairs)
+}
+
+fn distribution_to_json(dist: &Distribution) -> Value {
+    let (kind, params) = match *dist {
+        Distribution::Fixed(bytes) => ("fixed", Value::from(bytes)),
+        Distribution::Exponential { mean } => ("exponential", Value::from(mean)),
+        Distribution::Uniform { min, max } => (
+            "uniform",
+            Value::Object(vec![
+                ("min".to_string(), Value::from(min)),
+                ("max".to_string(), Value::from(max)),
+            ]),
+        ),
+        Distribution::LogNormal { mu, sigma } => (
+            "lognormal",
+            Value::Object(vec![
+                ("mu".to_string(), Value::from(mu)),
+                ("sigma".to_string(), Value::from(sigma)),
+            ]),
+        ),
+    };
+    Value::Object(vec![(kind.to_string(), params)])
+}
+
+fn parse_zone(name: &str) -> Option<Zone> {
+    match name.as_bytes() {
+        &[b] if b.is_ascii_graphic() => Some(Zone(b)),
+        _ => None,
+    }
+}
+
+fn optional_u64(obj: &Value, key: &str) -> Result<Option<u64>, ScenarioError> {
+    match obj.get(key) {
+        None => Ok(None),
+        Some(v) => v
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| field_error(key, "expected a non-negative integer")),
+    }
+}
+
+fn optional_f64(obj: &Value, parent: &str, key: &str) -> Result<Option<f64>, ScenarioError> {
+    match obj.get(key) {
+        None => Ok(None),
+        Some(v) => v
+            .as_f64()
+            .map(Some)
+            .ok_or_else(|| field_error(format!("{parent}.{key}"), "expected a number")),
+    }
+}
+
+/// Like [`optional_f64`], but rejecting negative and non-finite values.
+fn optional_non_negative(
+    obj: &Value,
+    parent: &str,
+    key: &str,
+) -> Result<Option<f64>, ScenarioError> {
+    optional_f64(obj, parent, key)?
+        .map(|x| non_negative(x, &format!("{parent}.{key}")))
+        .transpose()
+}
+
+fn non_negative(x: f64, field: &str) -> Result<f64, ScenarioError> {
+    if x.is_finite() && x >= 0.0 {
+        Ok(x)
+    } else {
+        Err(field_error(field, "expected a non-negative number"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_and_round_trips() {
+        let scenario = Scenario::parse(
+            r#"{
+                "name": "backendless d",
+                "iterations": 1000,
+                "zones": {
+                    "a": { "backends": 1 },
+                    "b": { "backends": 5, "capacity": 2 },
+                    "c": { "backends": 2, "clients": 0 },
+                    "d": { "clients": 2 }
+                },
+                "backends": [{ "id": 100, "zone": "a", "capacity": 0.5 }],
+                "egress": {
+                    "pairs": [{ "from": "a", "to": "d", "per_gb": 0.02 }],
+                    "response_bytes": { "lognormal": { "mu": 9, "sigma": 1 } }
+                },
+                "network": { "jitter_ms": { "uniform": { "min": 0, "max": 0.5 } } }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(scenario.strategy, Strategy::Picker);
+        assert_eq!(scenario.seed, DEFAULT_SEED);
+        let backends = scenario.backends();
+        assert_eq!(backends.len(), 9);
+        assert_eq!(backends.last().unwrap().id, BackendId(100));
+        let demand: Vec<_> = scenario.demand().into_iter().collect();
+        assert_eq!(
+            demand,
+            [(Zone(b'a'), 1.0), (Zone(b'b'), 1.0), (Zone(b'd'), 2.0)]
+        );
+
+        let model = scenario.egress.as_ref().unwrap();
+        assert_eq!(model.price(Zone(b'a'), Zone(b'd')), 0.02);
+        assert_eq!(model.price(Zone(b'd'), Zone(b'a')), 0.01);
+
+        let reparsed = Scenario::parse(&scenario.to_json().to_string()).unwrap();
+        assert_eq!(reparsed, scenario);
+        let report = scenario.run();
+        assert_eq!(report.total, 3 * 1000);
+        let egress = report.egress.unwrap().total;
+        assert!(0.0 < egress && egress < report.zone_blind_egress.unwrap().total);
+        let rtt = report.rtt_ms.unwrap();
+        assert!(0.25 < rtt && rtt < report.zone_blind_rtt_ms.unwrap());
+    }
+
+    #[test]
+    fn reports_bad_fields() {
+        let err = |text| Scenario::parse(text).unwrap_err().to_string();
+        assert_eq!(err(r#"{}"#), "zones: expected an object keyed by zone name");
+        assert_eq!(
+            err(r#"{"zones": {"ab": {}}}"#),
+            "zones.ab: zone names must be a single ASCII character"
+        );
+        assert_eq!(
+            err(r#"{"zones": {"a": {"backends": 1, "capacity": "x"}}}"#),
+            "zones.a.capacity: expected a number"
+        );
+        let names: Vec<_> = Strategy::ALL.iter().map(|s| s.name()).collect();
+        assert_eq!(
+            err(r#"{"strategy": "nope", "zones": {"a": {"backends": 1}}}"#),
+            format!(
+                "strategy: unknown strategy \"nope\", expected one of {}",
+                names.join(", ")
+            )
+        );
+        assert_eq!(
+            err(r#"{"zones": {"a": {}}, "egress": {"request_bytes": {"pareto": 1}}}"#),
+            "egress.request_bytes.pareto: expected one of {\"fixed\": n}, \
+             {\"uniform\": {\"min\", \"max\"}}, {\"exponential\": mean} or \
+             {\"lognormal\": {\"mu\", \"sigma\"}}"
+        );
+        assert_eq!(
+            err(r#"{"zones": {"a": {"backends": 1, "capacity": -1}}}"#),
+            "backend 0 has invalid capacity -1"
+        );
+        assert_eq!(
+            err(r#"{"zones": {"a": {"backends": 1}, "b": {"clients": -3}}}"#),
+            "zones.b.clients: expected a non-negative number"
+        );
+        assert_eq!(
+            err(r#"{"iterations": 0, "zones": {"a": {"backends": 1}}}"#),
+            "iterations: expected a positive integer"
+        );
+        assert_eq!(
+            err(r#"{"zones": {"a": {}}, "egress": {"cross_zone_per_gb": -0.01}}"#),
+            "egress.cross_zone_per_gb: expected a non-negative number"
+        );
+        assert_eq!(
+            err(r#"{"zones": {"a": {}}, "egress": {"request_bytes": {"fixed": -100}}}"#),
+            "egress.request_bytes.fixed: expected a non-negative number"
+        );
+        assert_eq!(
+            err(
+                r#"{"zones": {"a": {}}, "network": {"pairs": [{"from": "a", "to": "b", "ms": -1}]}}"#
+            ),
+            "network.pairs[0].ms: expected a non-negative number"
+        );
+    }
+}
@@ -0,0 +1,631 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use crate::{
+    cost::{self, CostModel, EgressCost},
+    error,
+    matrix::TrafficMatrix,
+    network::RttModel,
+    zonal, Backend, BackendId, LoadBalancer, PickerError, Zone,
+};
+
+/// Derive an independent seed for stream `index` (e.g. one client) from a
+/// simulation-level master seed, using the SplitMix64 finalizer.
+pub fn derive_seed(master: u64, index: u64) -> u64 {
+    let mut z = master.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Split `total` requests among clients in proportion to `rates` by the
+/// largest-remainder method, so that the counts always sum to `total`. Ties
+/// go to the earlier client. If no client has a positive rate, nobody sends.
+pub fn apportion(total: u64, rates: &[f64]) -> Vec<u64> {
+    let sum: f64 = rates.iter().sum();
+    if sum.is_nan() || sum <= 0.0 {
+        return vec![0; rates.len()];
+    }
+    let quotas: Vec<f64> = rates.iter().map(|r| total as f64 * r / sum).collect();
+    let mut counts: Vec<u64> = quotas.iter().map(|q| q.floor() as u64).collect();
+    let assigned: u64 = counts.iter().sum();
+    let mut by_remainder: Vec<usize> = (0..rates.len()).collect();
+    by_remainder.sort_by(|&a, &b| {
+        let remainder = |i: usize| quotas[i] - quotas[i].floor();
+        remainder(b).total_cmp(&remainder(a)).then(a.cmp(&b))
+    });
+    for &i in by_remainder
+        .iter()
+        .take(total.saturating_sub(assigned) as usize)
+    {
+        counts[i] += 1;
+    }
+    counts
+}
+
+/// The load balancing strategies a simulation can be run with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    Picker,
+    Dynamic,
+    /// Power of two choices by outstanding requests.
+    P2C,
+    /// Power of two choices by backend-reported utilization.
+    P2CReported,
+    /// Power of two choices by Peak-EWMA latency.
+    PeakEwma,
+    /// Smooth weighted round-robin over the picker's weights.
+    SmoothWrr,
+    /// Envoy's earliest-deadline-first weighted round-robin.
+    Edf,
+}
+impl Strategy {
+    pub const ALL: &'static [Strategy] = &[
+        Strategy::Picker,
+        Strategy::Dynamic,
+        Strategy::P2C,
+        Strategy::P2CReported,
+        Strategy::PeakEwma,
+        Strategy::SmoothWrr,
+        Strategy::Edf,
+    ];
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::Picker => "picker",
+            Strategy::Dynamic => "dynamic",
+            Strategy::P2C => "p2c",
+            Strategy::P2CReported => "p2c-reported",
+            Strategy::PeakEwma => "peak-ewma",
+            Strategy::SmoothWrr => "smooth-wrr",
+            Strategy::Edf => "edf",
+        }
+    }
+    /// Whether the strategy steers by what it hears back about requests in
+    /// flight. Such a strategy only differs from [`Strategy::Picker`] when
+    /// requests complete, so it can only be measured with [`crate::des`].
+    pub fn needs_feedback(self) -> bool {
+        matches!(
+            self,
+            Strategy::P2C | Strategy::P2CReported | Strategy::PeakEwma
+        )
+    }
+}
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+impl FromStr for Strategy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Strategy::ALL
+            .iter()
+            .copied()
+            .find(|strategy| strategy.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Strategy::ALL.iter().map(|s| s.name()).collect();
+                format!(
+                    "unknown strategy {s:?}, expected one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Evaluate `$body` with the type `$L` standing for the load balancer that
+/// `$strategy` names. This is the one place a [`Strategy`] is mapped to a type.
+macro_rules! with_strategy {
+    ($strategy:expr, $L:ident => $body:expr) => {
+        match $strategy {
+            $crate::sim::Strategy::Picker => {
+                type $L = $crate::Picker;
+                $body
+            }
+            $crate::sim::Strategy::Dynamic => {
+                type $L = $crate::DynamicPicker;
+                $body
+            }
+            $crate::sim::Strategy::P2C => {
+                type $L = $crate::P2CPicker;
+                $body
+            }
+            $crate::sim::Strategy::P2CReported => {
+                type $L = $crate::P2CPicker<$crate::p2c::Reported>;
+                $body
+            }
+            $crate::sim::Strategy::PeakEwma => {
+                type $L = $crate::PeakEwmaPicker;
+                $body
+            }
+            $crate::sim::Strategy::SmoothWrr => {
+                type $L = $crate::SmoothPicker;
+                $body
+            }
+            $crate::sim::Strategy::Edf => {
+                type $L = $crate::EdfPicker;
+                $body
+            }
+        }
+    };
+}
+pub(crate) use with_strategy;
+
+/// Build `count` backends of the given capacity in each zone, numbering ids consecutively.
+pub fn topology(zones: &[(Zone, usize, f64)]) -> Vec<Backend> {
+    zones
+        .iter()
+        .flat_map(|&(zone, count, capacity)| std::iter::repeat_n((zone, capacity), count))
+        .enumerate()
+        .map(|(idx, (zone, capacity))| Backend {
+            id: BackendId(idx as u32),
+            zone,
+            capacity,
+        })
+        .collect()
+}
+
+/// Scale each backend's amount of traffic by its share of total capacity, so
+/// that a perfectly balanced split has every entry at 1.0. `amounts` may be
+/// request counts or fractions of all requests. Backends with no capacity are
+/// omitted.
+pub fn normalized_load(
+    amounts: impl IntoIterator<Item = (BackendId, f64)>,
+    backends: &BTreeMap<BackendId, Backend>,
+) -> BTreeMap<BackendId, f64> {
+    let amounts: Vec<_> = amounts.into_iter().collect();
+    let total: f64 = amounts.iter().map(|(_, amount)| amount).sum();
+    let total_capacity: f64 = backends.values().map(|b| b.capacity).sum();
+    amounts
+        .into_iter()
+        .filter(|(id, _)| backends[id].capacity > 0.0)
+        .map(|(id, amount)| {
+            // Divide first: the product of two large finite values overflows.
+            let fair = total * (backends[&id].capacity / total_capacity);
+            (id, amount / fair)
+        })
+        .collect()
+}
+
+/// The outcome of routing a fixed number of requests from each client.
+#[derive(Clone, Debug)]
+pub struct Report {
+    /// The master seed; client `i` was seeded with `derive_seed(seed, i)`.
+    pub seed: u64,
+    pub iterations: u64,
+    pub backends: BTreeMap<BackendId, Backend>,
+    /// How many requests each backend received.
+    pub tally: BTreeMap<BackendId, u64>,
+    /// Per client zone, how many requests were sent and how many stayed in-zone.
+    pub clients: BTreeMap<Zone, ClientTally>,
+    /// Requests sent from each client zone to each backend zone.
+    pub flows: TrafficMatrix,
+    pub in_zone: u64,
+    pub total: u64,
+    /// Requests for which the client had no backend to pick.
+    pub unrouted: u64,
+    /// The egress bill for `flows`, if the run was priced with [`Report::with_egress`].
+    pub egress: Option<EgressCost>,
+    /// The bill for the same requests routed without regard to zones.
+    pub zone_blind_egress: Option<EgressCost>,
+    /// Mean round-trip milliseconds per request, if the run was given a
+    /// network model with [`Report::with_network`].
+    pub rtt_ms: Option<f64>,
+    /// Mean round trip for the same requests routed without regard to zones.
+    pub zone_blind_rtt_ms: Option<f64>,
+}
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ClientTally {
+    pub requests: u64,
+    pub in_zone: u64,
+}
+impl Report {
+    /// An empty report over `backends`, for a run of `iterations` requests per
+    /// client zone with master seed `seed`.
+    pub fn new(backends: &[Backend], seed: u64, iterations: u64) -> Self {
+        Self {
+            seed,
+            iterations,
+            backends: backends.iter().map(|b| (b.id, b.clone())).collect(),
+            tally: backends.iter().map(|b| (b.id, 0)).collect(),
+            clients: BTreeMap::new(),
+            flows: TrafficMatrix::new(),
+            in_zone: 0,
+            total: 0,
+            unrouted: 0,
+            egress: None,
+            zone_blind_egress: None,
+            rtt_ms: None,
+            zone_blind_rtt_ms: None,
+        }
+    }
+    /// Count one request from a client in `client_zone` that went to
+    /// `backend`, or that could not be routed if `backend` is `None`.
+    pub fn record(&mut self, client_zone: Zone, backend: Option<BackendId>) {
+        let Some(b) = backend else {
+            self.unrouted += 1;
+            return;
+        };
+        *self.tally.entry(b).or_default() += 1;
+        let backend_zone = self.backends[&b].zone;
+        self.flows.add(client_zone, backend_zone, 1.0);
+        let client = self.clients.entry(client_zone).or_default();
+        client.requests += 1;
+        if backend_zone == client_zone {
+            client.in_zone += 1;
+            self.in_zone += 1;
+        }
+        self.total += 1;
+    }
+    /// Each backend's request count relative to its share of total capacity,
+    /// so a perfectly balanced run has every entry at 1.0. Backends with no
+    /// capacity are omitted.
+    pub fn normalized_load(&self) -> BTreeMap<BackendId, f64> {
+        let counts = self.tally.iter().map(|(&id, &n)| (id, n as f64));
+        normalized_load(counts, &self.backends)
+    }
+    pub fn in_zone_fraction(&self) -> f64 {
+        self.in_zone as f64 / self.total as f64
+    }
+    /// Price this run's traffic, and the zone-blind baseline, with `model`.
+    pub fn with_egress(mut self, model: &CostModel) -> Self {
+        self.egress = Some(model.egress(&self.flows));
+        self.zone_blind_egress = Some(model.egress(&self.zone_blind_flows()));
+        self
+    }
+    /// Work out the mean round trip of this run's traffic, and of the
+    /// zone-blind baseline, under `model`.
+    pub fn with_network(mut self, model: &RttModel) -> Self {
+        self.rtt_ms = Some(model.mean_per_request(&self.flows));
+        self.zone_blind_rtt_ms = Some(model.mean_per_request(&self.zone_blind_flows()));
+        self
+    }
+    fn zone_blind_flows(&self) -> TrafficMatrix {
+        let backends: Vec<Backend> = self.backends.values().cloned().collect();
+        let demand = self
+            .clients
+            .iter()
+            .map(|(&zone, t)| (zone, t.requests as f64))
+            .collect();
+        cost::zone_blind_flows(&backends, &demand, self.total as f64)
+    }
+    /// The fraction of requests from clients in `zone` that stayed in-zone.
+    pub fn client_in_zone_fraction(&self, zone: Zone) -> f64 {
+        let t = self.clients.get(&zone).copied().unwrap_or_default();
+        t.in_zone as f64 / t.requests as f64
+    }
+}
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "seed = {}", self.seed)?;
+        for (id, load) in self.normalized_load() {
+            writeln!(f, "[{}] {load:.5}", self.backends[&id].zone)?;
+        }
+        write!(f, "% in-zone = {}", self.in_zone_fraction())?;
+        if let (Some(egress), Some(zone_blind)) = (&self.egress, &self.zone_blind_egress) {
+            write!(
+                f,
+                "\negress = ${:.4} (zone-blind = ${:.4})",
+                egress.total, zone_blind.total
+            )?;
+        }
+        if let (Some(rtt), Some(zone_blind)) = (self.rtt_ms, self.zone_blind_rtt_ms) {
+            write!(f, "\nrtt = {rtt:.3}ms (zone-blind = {zone_blind:.3}ms)")?;
+        }
+        Ok(())
+    }
+}
+
+/// One client's load balancer, and the relative rate at which it sends requests.
+pub struct Client<L> {
+    pub zone: Zone,
+    pub rate: f64,
+    pub lb: L,
+}
+
+/// One client per zone in `demand`, each seeded with `derive_seed(seed, i)`
+/// where `i` is the zone's position in `demand`.
+pub fn fleet<L: LoadBalancer>(
+    backends: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+    seed: u64,
+) -> Vec<Client<L>> {
+    demand
+        .iter()
+        .enumerate()
+        .map(|(idx, (&zone, &rate))| Client {
+            zone,
+            rate,
+            lb: L::with_demand(
+                zone,
+                backends.to_vec(),
+                demand,
+                derive_seed(seed, idx as u64),
+            ),
+        })
+        .collect()
+}
+
+/// Route `iterations` requests from a client in each of `client_zones`, using
+/// a fresh `L` per client with its own seed derived from `seed`. Every client
+/// knows about every zone in `client_zones`, even those without backends.
+pub fn run<L: LoadBalancer>(
+    backends: &[Backend],
+    client_zones: &[Zone],
+    iterations: u64,
+    seed: u64,
+) -> Report {
+    let demand = zonal::uniform_demand(client_zones.iter().copied());
+    run_with_demand::<L>(backends, &demand, iterations, seed)
+}
+
+/// Like [`run`], but with clients in each zone generating traffic in
+/// proportion to `demand`. The run sends `iterations` requests per zone on
+/// average, so skewed demand moves requests between zones without changing
+/// the total.
+pub fn run_with_demand<L: LoadBalancer>(
+    backends: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+    iterations: u64,
+    seed: u64,
+) -> Report {
+    let mut report = Report::new(backends, seed, iterations);
+    let fleet = fleet::<L>(backends, demand, seed);
+    let rates: Vec<f64> = fleet.iter().map(|c| c.rate).collect();
+    let counts = apportion(iterations * demand.len() as u64, &rates);
+    for (
+        Client {
+            zone: client_zone,
+            mut lb,
+            ..
+        },
+        requests,
+    ) in fleet.into_iter().zip(counts)
+    {
+        report.clients.entry(client_zone).or_default();
+        for _ in 0..requests {
+            report.record(client_zone, lb.sample());
+        }
+    }
+    report
+}
+
+/// Check that a client in every zone of `demand` could build a picker over `backends`.
+pub fn validate(backends: &[Backend], demand: &BTreeMap<Zone, f64>) -> Result<(), PickerError> {
+    for &zone in demand.keys() {
+        error::validate(zone, backends)?;
+    }
+    error::validate_demand(demand)
+}
+
+/// [`run_with_demand`] for a strategy chosen at runtime.
+///
+/// # Panics
+///
+/// If `strategy` [needs feedback](Strategy::needs_feedback), which a run
+/// without request completions never gives it.
+pub fn run_strategy(
+    strategy: Strategy,
+    backends: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+    iterations: u64,
+    seed: u64,
+) -> Report {
+    assert_no_feedback(strategy);
+    with_strategy!(strategy, L => run_with_demand::<L>(backends, demand, iterations, seed))
+}
+
+/// How unevenly load lands on backends over short windows of requests.
+#[derive(Clone, Copy, Debug)]
+pub struct WindowSpread {
+    /// Requests per window, summed over every client.
+    pub window: u64,
+    pub windows: u64,
+    /// The mean, over windows, of the most loaded backend's normalized load
+    /// divided by the least loaded's. 1.0 means every window was exact.
+    pub mean: f64,
+    /// The largest spread of any window.
+    pub worst: f64,
+}
+impl fmt::Display for WindowSpread {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "max/min over {} windows of {} = {:.5} mean, {:.5} worst",
+            self.windows, self.window, self.mean, self.worst
+        )
+    }
+}
+
+/// Send `windows` consecutive windows of `window` requests from the fleet,
+/// each client sending its share of every window, and measure the max/min
+/// spread of normalized load within each window. Random pickers are only
+/// balanced on average; deterministic schedulers are balanced in every window.
+///
+/// # Panics
+///
+/// If `window` is smaller than the number of client zones in `demand`, since
+/// some clients would then send nothing at all.
+pub fn window_spread<L: LoadBalancer>(
+    backends: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+    window: u64,
+    windows: u64,
+    seed: u64,
+) -> WindowSpread {
+    assert!(
+        window >= demand.len() as u64,
+        "a window of {window} requests is smaller than the {} clients",
+        demand.len()
+    );
+    let mut fleet = fleet::<L>(backends, demand, seed);
+    let rates: Vec<f64> = fleet.iter().map(|c| c.rate).collect();
+    let counts = apportion(window, &rates);
+    let by_id: BTreeMap<BackendId, Backend> = backends.iter().map(|b| (b.id, b.clone())).collect();
+    let mut spreads = Vec::with_capacity(windows as usize);
+    for _ in 0..windows {
+        let mut tally: BTreeMap<BackendId, u64> = backends.iter().map(|b| (b.id, 0)).collect();
+        for (client, &requests) in fleet.iter_mut().zip(&counts) {
+            for _ in 0..requests {
+                if let Some(b) = client.lb.sample() {
+                    *tally.entry(b).or_default() += 1;
+                }
+            }
+        }
+        let sent = tally.into_iter().map(|(id, n)| (id, n as f64));
+        let (min, max) = normalized_load(sent, &by_id)
+            .into_values()
+            .fold((f64::INFINITY, 0.0_f64), |(min, max), load| {
+                (min.min(load), max.max(load))
+            });
+        spreads.push(max / min);
+    }
+    WindowSpread {
+        window,
+        windows,
+        mean: spreads.iter().sum::<f64>() / spreads.len() as f64,
+        worst: spreads.iter().copied().fold(0.0, f64::max),
+    }
+}
+
+/// [`window_spread`] for a strategy chosen at runtime.
+///
+/// # Panics
+///
+/// As [`window_spread`], or if `strategy` [needs
+/// feedback](Strategy::needs_feedback).
+pub fn window_spread_strategy(
+    strategy: Strategy,
+    backends: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+    window: u64,
+    windows: u64,
+    seed: u64,
+) -> WindowSpread {
+    assert_no_feedback(strategy);
+    with_strategy!(strategy, L => window_spread::<L>(backends, demand, window, windows, seed))
+}
+
+/// Refuse to sample a strategy that would silently behave like another
+/// because nothing ever reports back to it.
+pub(crate) fn assert_no_feedback(strategy: Strategy) {
+    assert!(
+        !strategy.needs_feedback(),
+        "strategy {strategy} needs request completions, which only the discrete-event simulator gives"
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Picker, SmoothPicker};
+
+    #[test]
+    fn seeds_are_reproducible_and_independent() {
+        let backends: Vec<Backend> = (0..8)
+            .map(|i| Backend {
+                id: BackendId(i),
+                zone: Zone(b'a' + (i % 2) as u8),
+                capacity: 1.0,
+            })
+            .collect();
+        let draw = |seed| {
+            let mut picker = Picker::with_seed(Zone(b'a'), backends.clone(), seed);
+            (0..32)
+                .map(|_| picker.sample().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(draw(7), draw(7));
+        assert_ne!(draw(derive_seed(7, 0)), draw(derive_seed(7, 1)));
+
+        let zones = [Zone(b'a'), Zone(b'b')];
+        let a = run::<Picker>(&backends, &zones, 1_000, 7);
+        let b = run::<Picker>(&backends, &zones, 1_000, 7);
+        assert_eq!(a.tally, b.tally);
+        assert!(a.to_string().starts_with("seed = 7\n"));
+    }
+
+    #[test]
+    fn apportioned_counts_sum_to_the_total() {
+        assert_eq!(apportion(10, &[1.0, 1.0, 1.0]), [4, 3, 3]);
+        assert_eq!(apportion(2, &[1.0, 1.0, 1.0]), [1, 1, 0]);
+        assert_eq!(apportion(7, &[0.6, 0.2, 0.2]), [4, 2, 1]);
+        assert_eq!(apportion(5, &[0.0, 0.0]), [0, 0]);
+    }
+
+    #[test]
+    fn skewed_demand_keeps_load_uniform() {
+        // Three equally sized zones, but 60% of the traffic comes from zone A.
+        let backends: Vec<Backend> = (0..9)
+            .map(|i| Backend {
+                id: BackendId(i),
+                zone: Zone(b'a' + (i / 3) as u8),
+                capacity: 1.0,
+            })
+            .collect();
+        let demand = [(Zone(b'a'), 0.6), (Zone(b'b'), 0.2), (Zone(b'c'), 0.2)]
+            .into_iter()
+            .collect();
+
+        let report = run_with_demand::<Picker>(&backends, &demand, 100_000, 7);
+
+        for (id, load) in report.normalized_load() {
+            assert!((0.95..=1.05).contains(&load), "{id:?}: load = {load}");
+        }
+        // A can only serve 1/3 of the traffic locally, so it keeps (1/3) / 0.6
+        // of its own requests and spills the rest evenly into B and C.
+        let a = report.client_in_zone_fraction(Zone(b'a'));
+        assert!((a - 5.0 / 9.0).abs() < 0.01, "a in-zone = {a}");
+        assert_eq!(report.client_in_zone_fraction(Zone(b'b')), 1.0);
+        assert_eq!(report.client_in_zone_fraction(Zone(b'c')), 1.0);
+    }
+
+    #[test]
+    fn capacity_is_a_relative_weight() {
+        let shape = [(Zone(b'a'), 2, 1.0), (Zone(b'b'), 3, 2.0)];
+        let zones = [Zone(b'a'), Zone(b'b'), Zone(b'c')];
+        let small = run::<Picker>(&topology(&shape), &zones, 10_000, 7);
+        // Each capacity is finite and so is their sum, which is just under
+        // f64::MAX, so fair shares must not be computed as `total * capacity`.
+        let huge = shape.map(|(zone, n, capacity)| (zone, n, capacity * 2e307));
+        let huge = run::<Picker>(&topology(&huge), &zones, 10_000, 7);
+        for ((id, a), b) in small
+            .normalized_load()
+            .into_iter()
+            .zip(huge.normalized_load().into_values())
+        {
+            assert!((a - b).abs() < 1e-9, "{id:?}: {a} vs {b}");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "strategy p2c needs request completions")]
+    fn feedback_strategies_need_the_event_simulator() {
+        let backends = topology(&[(Zone(b'a'), 2, 1.0)]);
+        let demand = zonal::uniform_demand([Zone(b'a')]);
+        run_strategy(Strategy::P2C, &backends, &demand, 100, 7);
+    }
+
+    #[test]
+    fn round_robin_is_balanced_in_every_window() {
+        let backends = topology(&[
+            (Zone(b'a'), 1, 1.0),
+            (Zone(b'b'), 5, 1.0),
+            (Zone(b'c'), 9, 1.0),
+        ]);
+        let demand = zonal::uniform_demand([Zone(b'a'), Zone(b'b'), Zone(b'c')]);
+        let random = window_spread::<Picker>(&backends, &demand, 1_500, 50, 7);
+        let smooth = window_spread::<SmoothPicker>(&backends, &demand, 1_500, 50, 7);
+        // Each backend's fair share is 100 requests a window, and random
+        // picks spread them by about +-10%, while round-robin is off by at
+        // most one pick per client.
+        assert!(random.mean > 1.3, "random = {random}");
+        assert!(smooth.worst < 1.05, "smooth = {smooth}");
+    }
+
+    #[test]
+    #[should_panic(expected = "smaller than the 3 clients")]
+    fn windows_need_a_request_per_client() {
+        let backends = topology(&[(Zone(b'a'), 1, 1.0), (Zone(b'b'), 1, 1.0)]);
+        let demand = zonal::uniform_demand([Zone(b'a'), Zone(b'b'), Zone(b'c')]);
+        window_spread::<SmoothPicker>(&backends, &demand, 2, 10, 7);
+    }
+}
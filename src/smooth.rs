@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+
+use crate::{zonal, Backend, BackendId, LoadBalancer, Zone};
+
+/// nginx's smooth weighted round-robin over the zonal-affinity weights.
+///
+/// Each pick adds every backend's weight to its running credit, sends the
+/// request to the backend with the most credit, and charges that backend the
+/// total weight. Over any run of picks each backend's count stays within one
+/// of its exact share, and heavy backends are interleaved with light ones
+/// rather than picked in bursts. There is no randomness, so the seed is unused.
+#[derive(Clone, Debug)]
+pub struct SmoothPicker {
+    // Backends with a positive weight, their weights, and their credit.
+    entries: Vec<(BackendId, f64, f64)>,
+    total_weight: f64,
+}
+impl SmoothPicker {
+    pub fn from_weights(weights: impl IntoIterator<Item = (BackendId, f64)>) -> Self {
+        let entries: Vec<_> = weights
+            .into_iter()
+            .filter(|&(_, w)| w > 0.0)
+            .map(|(id, w)| (id, w, 0.0))
+            .collect();
+        Self {
+            total_weight: entries.iter().map(|e| e.1).sum(),
+            entries,
+        }
+    }
+}
+impl LoadBalancer for SmoothPicker {
+    fn with_demand(
+        zone: Zone,
+        backends: Vec<Backend>,
+        demand: &BTreeMap<Zone, f64>,
+        _seed: u64,
+    ) -> Self {
+        Self::from_weights(zonal::backend_weights(zone, &backends, demand))
+    }
+    fn sample(&mut self) -> Option<BackendId> {
+        let mut best: Option<(usize, f64)> = None;
+        for (i, (_, weight, credit)) in self.entries.iter_mut().enumerate() {
+            *credit += *weight;
+            if best.is_none_or(|(_, most)| *credit > most) {
+                best = Some((i, *credit));
+            }
+        }
+        let (i, _) = best?;
+        self.entries[i].2 -= self.total_weight;
+        Some(self.entries[i].0)
+    }
+    fn weights(&self) -> BTreeMap<BackendId, f64> {
+        self.entries
+            .iter()
+            .map(|&(id, w, _)| (id, w / self.total_weight))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sim;
+
+    #[test]
+    fn interleaves_by_weight() {
+        // nginx's example: weights 5, 1, 1 give a, a, b, a, c, a, a.
+        let mut lb = SmoothPicker::from_weights([
+            (BackendId(0), 5.0),
+            (BackendId(1), 1.0),
+            (BackendId(2), 1.0),
+        ]);
+        let picks: Vec<u32> = (0..14).map(|_| lb.sample().unwrap().0).collect();
+        assert_eq!(picks, [0, 0, 1, 0, 2, 0, 0, 0, 0, 1, 0, 2, 0, 0]);
+
+        // Zone A's client keeps its weights proportional to the picker's.
+        let backends = sim::topology(&[(Zone(b'a'), 1, 1.0), (Zone(b'b'), 5, 1.0)]);
+        let lb = SmoothPicker::new(Zone(b'a'), backends.clone());
+        let picker = crate::Picker::new(Zone(b'a'), backends);
+        for (id, w) in picker.weights() {
+            assert!((lb.weights()[&id] - w).abs() < 1e-12, "{id:?}");
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use crate::{Backend, BackendId, Zone};
+
+/// Sum the capacity of `backends` in each zone.
+pub fn per_zone_capacity<'a>(
+    backends: impl IntoIterator<Item = &'a Backend>,
+) -> BTreeMap<Zone, f64> {
+    let mut acc: BTreeMap<Zone, f64> = BTreeMap::new();
+    for b in backends {
+        *acc.entry(b.zone).or_default() += b.capacity;
+    }
+    acc
+}
+
+/// Equal demand from every zone in `zones`.
+pub fn uniform_demand(zones: impl IntoIterator<Item = Zone>) -> BTreeMap<Zone, f64> {
+    zones.into_iter().map(|z| (z, 1.0)).collect()
+}
+
+/// How a client in `zone` should scale the capacity of each backend, keyed by
+/// the backend's zone. Zones that should receive no traffic may be omitted.
+///
+/// `demand` holds the relative request rate coming from clients in each zone,
+/// including zones that have no backends of their own. Each zone is entitled
+/// to the same share of total capacity as its share of total demand; clients
+/// in zones with less than that send the shortfall to zones with more.
+///
+/// This mirrors Envoy's zone-aware routing: a client keeps
+/// `capacity share / demand share` of its traffic local when that ratio is
+/// below one, and spreads the remainder across zones in proportion to their
+/// residual capacity (`capacity share - demand share`). A client in a zone
+/// without backends, or missing from `demand`, keeps nothing local and
+/// spreads all of its traffic that way.
+pub fn zonal_multipliers(
+    zone: Zone,
+    per_zone_capacity: &BTreeMap<Zone, f64>,
+    demand: &BTreeMap<Zone, f64>,
+) -> BTreeMap<Zone, f64> {
+    let total_capacity: f64 = per_zone_capacity.values().sum();
+    let total_demand: f64 = demand.values().sum();
+    // The capacity each zone would need to serve its own clients.
+    let fair_capacity = |z: &Zone| {
+        if total_demand > 0.0 {
+            total_capacity * (demand.get(z).copied().unwrap_or_default() / total_demand)
+        } else {
+            total_capacity / per_zone_capacity.len() as f64
+        }
+    };
+    let my_fair_capacity = fair_capacity(&zone);
+    let my_zone_capacity = per_zone_capacity.get(&zone).copied().unwrap_or_default();
+    let surplus_capacity: f64 = per_zone_capacity
+        .iter()
+        .map(|(z, &cap)| {
+            let fair = fair_capacity(z);
+            if cap > fair {
+                cap - fair
+            } else {
+                0.0
+            }
+        })
+        .sum();
+    let known = demand.contains_key(&zone);
+    if known && my_zone_capacity > 0.0 && my_zone_capacity >= my_fair_capacity {
+        // If we are from an over-capacity zone, stay entirely in-zone.
+        [(zone, 1.0)].into_iter().collect()
+    } else if surplus_capacity <= 0.0 {
+        // Only a client nobody planned for, or one declared with no demand,
+        // gets here, when every zone has exactly its fair share. There is no
+        // surplus to use, so spread by capacity alone.
+        per_zone_capacity.keys().map(|&z| (z, 1.0)).collect()
+    } else {
+        // If we are from an under-capacity zone, we can't send _all_
+        // traffic in-zone or we'll overload our backends.  So we need to
+        // send some traffic in-zone and some cross-zone.
+        let in_zone = if known && my_fair_capacity > 0.0 {
+            my_zone_capacity / my_fair_capacity
+        } else {
+            0.0
+        };
+        let cross_zone = 1.0 - in_zone;
+        per_zone_capacity
+            .iter()
+            .filter(|(_, &zone_cap)| zone_cap > 0.0)
+            .map(|(&z, &zone_cap)| {
+                let fair = fair_capacity(&z);
+                let zone_weight = if z == zone && known {
+                    in_zone
+                } else if zone_cap <= fair {
+                    // If the target zone is under-capacity, don't send any traffic.
+                    0.0
+                } else {
+                    // Send cross-zone traffic proportional to how much of the surplus capacity
+                    // is present in that zone.
+                    cross_zone * (zone_cap - fair) / surplus_capacity
+                };
+                (z, zone_weight / zone_cap)
+            })
+            .collect()
+    }
+}
+
+/// Each backend's capacity scaled by the multiplier a client in `zone` applies
+/// to its zone, for schedulers that take weights directly. Backends that
+/// should receive no traffic are omitted.
+pub fn backend_weights(
+    zone: Zone,
+    backends: &[Backend],
+    demand: &BTreeMap<Zone, f64>,
+) -> Vec<(BackendId, f64)> {
+    let multipliers = zonal_multipliers(zone, &per_zone_capacity(backends), demand);
+    backends
+        .iter()
+        .filter_map(|b| {
+            let weight = multipliers.get(&b.zone)? * b.capacity;
+            (weight > 0.0).then_some((b.id, weight))
+        })
+        .collect()
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+
+use crate::{hash, zonal, Backend, BackendId, KeyedBalancer, RingHash, Zone};
+
+/// Keyed routing that keeps keys in the client's zone where it can.
+///
+/// A key first picks a zone, by hashing it onto the same per-zone
+/// probabilities [`crate::Picker`] uses, with the client's own zone first so
+/// that its keys stay put as the spill fraction changes. Within the zone, a
+/// `K` over that zone's backends picks the backend. Clients in the same zone
+/// therefore agree on every key.
+#[derive(Clone, Debug)]
+pub struct ZonalKeyed<K = RingHash> {
+    // Zones in the order keys are laid out over [0, 1), with the cumulative
+    // probability at the end of each.
+    zones: Vec<(Zone, f64)>,
+    per_zone: BTreeMap<Zone, K>,
+    weights: BTreeMap<BackendId, f64>,
+}
+impl<K: KeyedBalancer> ZonalKeyed<K> {
+    // Keeps the zone choice independent of the hash used within the zone.
+    const ZONE_SALT: u64 = 0x7a6f_6e65;
+
+    pub fn with_demand(zone: Zone, backends: Vec<Backend>, demand: &BTreeMap<Zone, f64>) -> Self {
+        let per_zone_capacity = zonal::per_zone_capacity(&backends);
+        let multipliers = zonal::zonal_multipliers(zone, &per_zone_capacity, demand);
+        let zone_weight =
+            |z: &Zone| multipliers.get(z).copied().unwrap_or_default() * per_zone_capacity[z];
+        let total: f64 = per_zone_capacity.keys().map(zone_weight).sum();
+
+        let order = per_zone_capacity
+            .keys()
+            .filter(|&&z| z == zone)
+            .chain(per_zone_capacity.keys().filter(|&&z| z != zone));
+        let mut zones = Vec::new();
+        let mut cumulative = 0.0;
+        for z in order {
+            let p = zone_weight(z) / total;
+            if p > 0.0 {
+                cumulative += p;
+                zones.push((*z, cumulative));
+            }
+        }
+
+        let mut weights = BTreeMap::new();
+        let mut per_zone = BTreeMap::new();
+        for &(z, _) in &zones {
+            let members: Vec<Backend> = backends.iter().filter(|b| b.zone == z).cloned().collect();
+            for b in &members {
+                weights.insert(
+                    b.id,
+                    zone_weight(&z) / total * b.capacity / per_zone_capacity[&z],
+                );
+            }
+            per_zone.insert(z, K::from_backends(members));
+        }
+        Self {
+            zones,
+            per_zone,
+            weights,
+        }
+    }
+    /// The fraction of keys routed to each backend, assuming the hash within
+    /// each zone splits keys in proportion to capacity.
+    pub fn weights(&self) -> BTreeMap<BackendId, f64> {
+        self.weights.clone()
+    }
+    /// The zone `key` is routed to.
+    pub fn zone_of(&self, key: &[u8]) -> Option<Zone> {
+        let h = hash::hash_with(key, Self::ZONE_SALT);
+        let u = (h >> 11) as f64 / (1u64 << 53) as f64;
+        self.zones
+            .iter()
+            .find(|&&(_, end)| u < end)
+            .or(self.zones.last())
+            .map(|&(z, _)| z)
+    }
+    pub fn sample_key(&mut self, key: &[u8]) -> Option<BackendId> {
+        let zone = self.zone_of(key)?;
+        self.per_zone.get_mut(&zone)?.sample_key(key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{keyed, sim, Rendezvous};
+
+    #[test]
+    fn keeps_keys_local_and_spills_by_surplus() {
+        // Zone A holds a quarter of the capacity but half the demand, so its
+        // clients keep half their keys and spill the rest into B.
+        let backends = sim::topology(&[(Zone(b'a'), 1, 1.0), (Zone(b'b'), 3, 1.0)]);
+        let demand = [(Zone(b'a'), 1.0), (Zone(b'b'), 1.0)].into_iter().collect();
+        let mut a = ZonalKeyed::<Rendezvous>::with_demand(Zone(b'a'), backends.clone(), &demand);
+        let local = (0..20_000)
+            .filter(|&i| a.sample_key(&keyed::key(1, i)) == Some(BackendId(0)))
+            .count();
+        assert!((9_700..10_300).contains(&local), "local = {local}");
+        assert_eq!(a.weights()[&BackendId(0)], 0.5);
+
+        let b = ZonalKeyed::<Rendezvous>::with_demand(Zone(b'b'), backends, &demand);
+        assert!((0..1_000).all(|i| b.zone_of(&keyed::key(1, i)) == Some(Zone(b'b'))));
+    }
+}